@@ -0,0 +1,8 @@
+#![deny(clippy::all)]
+//! A re-implementation of `jlox` from [Crafting Interpreters](https://craftinginterpreters.com/).
+//! [`Lox`] runs programs for a host, the modules underneath it are there for anything it
+//! doesn't cover.
+
+mod lox_rs;
+
+pub use lox_rs::{ast, convert, env, error, host, lexer, lox::Lox, output, parser};
@@ -1,11 +1,36 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
-use super::{super::expr::Literal, native_fn::NativeFn};
+use super::{
+	super::{
+		super::{
+			ast::visitors::interp::Interperter,
+			error::{ErrorKind, Exit},
+		},
+		expr::Literal,
+	},
+	native_fn::NativeFn,
+};
 
-pub const NOW: NativeFn = NativeFn::new("now", 0, |_inputs: Vec<Literal>| -> Result<Literal> {
+/// The natives every program can use, as globals
+pub fn builtins() -> [NativeFn; 2] {
+	[NativeFn::new("now", 0, now), NativeFn::new("exit", 1, exit)]
+}
+
+fn now(_interp: &mut Interperter, _inputs: Vec<Literal>) -> Result<Literal> {
 	let seconds = SystemTime::now().duration_since(UNIX_EPOCH)?;
 	let seconds = seconds.as_secs_f64();
 	Ok(Literal::Number(seconds))
-});
+}
+fn exit(_interp: &mut Interperter, inputs: Vec<Literal>) -> Result<Literal> {
+	match inputs[..] {
+		[Literal::Number(code)] if code.fract() == 0. && (0. ..=255.).contains(&code) => {
+			Err(Exit(code as i32).into())
+		}
+		ref other => bail!(ErrorKind::Value.error(format!(
+			"Please exit with a whole number from 0 to 255, not {:?}",
+			other
+		))),
+	}
+}
@@ -1,12 +1,59 @@
-use anyhow::Result;
+use std::fmt;
+
+use anyhow::{bail, Result};
 
 use super::super::{
-	super::ast::visitors::interp::Interperter,
-	expr::{Expr, Literal},
+	super::{ast::visitors::interp::Interperter, error::ErrorKind},
+	expr::Literal,
 };
 
-pub trait Callable {
-	fn arity(&self) -> usize;
-	fn call(&self, interp: &mut Interperter, args: Vec<Expr>) -> Result<Literal>;
-	fn to_string(&self) -> String;
+/// How many arguments a callable accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+	pub min: usize,
+	/// `None` if there's no upper limit (a `...rest` parameter)
+	pub max: Option<usize>,
+}
+impl Arity {
+	pub const fn exact(n: usize) -> Self {
+		Self {
+			min: n,
+			max: Some(n),
+		}
+	}
+	/// `n` or more
+	pub const fn at_least(n: usize) -> Self {
+		Self { min: n, max: None }
+	}
+	/// Make sure a call with `got` arguments fits, naming the callee otherwise
+	pub fn check(&self, callee: &str, got: usize) -> Result<()> {
+		if got < self.min || self.max.is_some_and(|max| got > max) {
+			bail!(ErrorKind::Arity.error(format!(
+				"`{}` expected {} arguments but got {}",
+				callee, self, got
+			)));
+		}
+		Ok(())
+	}
+}
+impl From<usize> for Arity {
+	fn from(n: usize) -> Self {
+		Self::exact(n)
+	}
+}
+impl fmt::Display for Arity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.max {
+			Some(max) if max == self.min => write!(f, "{max}"),
+			Some(max) => write!(f, "{} to {max}", self.min),
+			None => write!(f, "at least {}", self.min),
+		}
+	}
+}
+
+pub trait Callable: fmt::Display {
+	fn name(&self) -> String;
+	fn arity(&self) -> Arity;
+	/// Run the callable with arguments that were already evaluated and checked against its arity
+	fn call(&self, interp: &mut Interperter, args: Vec<Literal>) -> Result<Literal>;
 }
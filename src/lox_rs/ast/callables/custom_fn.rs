@@ -1,24 +1,69 @@
-use std::fmt;
+use std::{cell::RefCell, fmt, rc::Rc};
 
 use anyhow::{bail, Result};
 
 use super::{
 	super::{
 		super::{
-			ast::{expr::Expr, stmt::Stmt, visitors::interp::Interperter},
-			env::Env,
+			ast::{pattern::Params, stmt::Stmt, visitors::interp::Interperter},
+			env::{Env, SharedEnv, WeakEnv},
+			error::ErrorKind,
 		},
 		expr::Literal,
 	},
-	callable::Callable,
+	callable::{Arity, Callable},
 };
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone)]
 pub struct CustomFn {
 	name: String,
-	inputs: Vec<Expr>,
-	body: Box<Stmt>,
-	closure: Box<Env>,
+	inputs: Params,
+	body: Rc<Stmt>,
+	closure: Closure,
+}
+/// The scope a function was declared in. The function is stored in that same scope, so if that
+/// copy held on to it they would keep each other alive. It only holds on to it weakly, and the
+/// copies read out of the scope hold on to it again. Functions stored anywhere else that can
+/// reach their own scope, like a global list of callbacks, still keep it alive while the
+/// interpreter runs. Dropping the interpreter empties the scopes functions were declared in,
+/// which frees them.
+#[derive(Clone)]
+enum Closure {
+	Strong(SharedEnv),
+	Weak(WeakEnv),
+}
+impl Closure {
+	/// The scope, unless the function was only holding on to it weakly and it's gone
+	fn upgrade(&self) -> Option<SharedEnv> {
+		match self {
+			Self::Strong(env) => Some(env.clone()),
+			Self::Weak(env) => env.upgrade(),
+		}
+	}
+	fn as_ptr(&self) -> *const RefCell<Env> {
+		match self {
+			Self::Strong(env) => Rc::as_ptr(env),
+			Self::Weak(env) => env.as_ptr(),
+		}
+	}
+}
+// The closure usually holds the function itself, so it's compared by identity and not printed
+impl fmt::Debug for CustomFn {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("CustomFn")
+			.field("name", &self.name)
+			.field("inputs", &self.inputs)
+			.field("body", &self.body)
+			.finish_non_exhaustive()
+	}
+}
+impl PartialEq for CustomFn {
+	fn eq(&self, other: &Self) -> bool {
+		self.name == other.name
+			&& self.inputs == other.inputs
+			&& Rc::ptr_eq(&self.body, &other.body)
+			&& self.closure.as_ptr() == other.closure.as_ptr()
+	}
 }
 impl fmt::Display for CustomFn {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -30,17 +75,20 @@ impl fmt::Display for CustomFn {
 		} = self;
 		write!(
 			f,
-			"function {name}({inputs:?}) {{
+			"function {name}({inputs}) {{
                 {body:#?}
             }}",
 		)
 	}
 }
 impl Callable for CustomFn {
-	fn arity(&self) -> usize {
-		self.inputs.len()
+	fn name(&self) -> String {
+		self.name.to_string()
+	}
+	fn arity(&self) -> Arity {
+		self.inputs.arity()
 	}
-	fn call(&self, interp: &mut Interperter, args: Vec<Expr>) -> Result<Literal> {
+	fn call(&self, interp: &mut Interperter, args: Vec<Literal>) -> Result<Literal> {
 		let Self {
 			name,
 			inputs,
@@ -48,45 +96,77 @@ impl Callable for CustomFn {
 			closure,
 		} = self;
 
-		let mut closure = closure.clone();
-		closure.set_parent(Box::new(interp.global.clone()));
-
-		for (index, input) in inputs.iter().enumerate() {
-			let Expr::Variable(input_name) = input else {
-				bail!(
-					"Unexpected expression {:?} (should of been a variable)",
-					&input
-				);
-			};
-			let input_name = input_name.to_string();
-			let input = args
-				.get(index)
-				.unwrap_or_else(|| &Expr::Literal(Literal::Null));
-			let input = interp.expr(input)?;
+		let Some(closure) = closure.upgrade() else {
+			bail!(ErrorKind::Value.error(format!("The scope `{name}` was declared in is gone")));
+		};
+		let env = Env::new(closure).shared();
+		let mut values = args.into_iter();
 
-			closure.define(input_name, input);
+		// Parameters are bound inside the function's scope, so defaults are evaluated on every
+		// call and can refer to the parameters before them
+		let caller_env = std::mem::replace(&mut interp.local, env);
+		let bound = inputs
+			.elements
+			.iter()
+			.try_fold(true, |bound, input| -> Result<bool> {
+				let value = match (values.next(), &input.default) {
+					(Some(value), _) => value,
+					(None, Some(default)) => interp.expr(default)?,
+					(None, None) => bail!(ErrorKind::Arity
+						.error(format!("Missing an argument for `{}`", &input.pattern))),
+				};
+				Ok(bound && interp.bind_pattern(&input.pattern, value)?)
+			})
+			.and_then(|bound| {
+				if let Some(rest) = &inputs.rest {
+					let values = interp.list(values.collect())?;
+					interp.local.borrow_mut().define(rest.to_string(), values);
+				}
+				Ok(bound)
+			});
+		let env = std::mem::replace(&mut interp.local, caller_env);
+		if !bound? {
+			bail!(ErrorKind::Value.error(format!(
+				"Could not bind the arguments of `{}` to its parameters",
+				name
+			)));
 		}
-		closure.define(name.to_string(), Literal::CustomFunction(self.clone()));
-		log::debug!("{:?}", &closure);
+		log::debug!("{:?}", &env);
 
-		interp.exec_block(body.as_ref(), closure)
-	}
-	fn to_string(&self) -> String {
-		format!("{self}")
+		interp.exec_block(body.as_ref(), env)
 	}
 }
 impl CustomFn {
-	pub fn new(
-		name: String,
-		inputs: Vec<Expr>,
-		body: Box<Stmt>,
-		closure: Option<Box<Env>>,
-	) -> Self {
+	pub fn new(name: String, inputs: Params, body: Rc<Stmt>, closure: SharedEnv) -> Self {
 		Self {
 			name,
 			inputs,
 			body,
-			closure: closure.unwrap_or_default(),
+			closure: Closure::Strong(closure),
+		}
+	}
+	/// The copy to store in the scope the function was declared in, see [`Closure`]
+	pub fn downgrade(&self) -> Self {
+		let closure = match &self.closure {
+			Closure::Strong(env) => Closure::Weak(Rc::downgrade(env)),
+			Closure::Weak(env) => Closure::Weak(env.clone()),
+		};
+		Self {
+			closure,
+			..self.clone()
+		}
+	}
+	/// The copy to read out of a scope, which holds on to the scope it was declared in again
+	pub fn upgrade(&self) -> Self {
+		match &self.closure {
+			Closure::Weak(_) => match self.closure.upgrade() {
+				Some(env) => Self {
+					closure: Closure::Strong(env),
+					..self.clone()
+				},
+				None => self.clone(),
+			},
+			Closure::Strong(_) => self.clone(),
 		}
 	}
 }
@@ -1,4 +1,5 @@
 pub mod builtins;
 pub mod callable;
 pub mod custom_fn;
+pub mod module;
 pub mod native_fn;
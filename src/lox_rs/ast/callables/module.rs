@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+
+use super::{
+	super::{
+		super::ast::visitors::interp::Interperter,
+		expr::{Literal, Shared},
+	},
+	callable::{Arity, Callable},
+	native_fn::{NativeFn, TypedNative},
+};
+
+/// Natives grouped under one name. Registering it defines a global map of its functions, which
+/// Lox code calls like `math.max(1, 2)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NativeModule {
+	name: String,
+	functions: Vec<NativeFn>,
+}
+impl NativeModule {
+	pub fn new(name: impl Into<String>) -> Self {
+		Self {
+			name: name.into(),
+			functions: Vec::new(),
+		}
+	}
+	/// Add a function to the module
+	pub fn function(
+		mut self,
+		name: &str,
+		arity: impl Into<Arity>,
+		func: impl Fn(&mut Interperter, Vec<Literal>) -> Result<Literal> + 'static,
+	) -> Self {
+		self.functions.push(NativeFn::new(name, arity, func));
+		self
+	}
+	/// Add a plain Rust function to the module, see [`NativeFn::typed`]
+	pub fn typed<Args>(mut self, name: &str, func: impl TypedNative<Args> + 'static) -> Self {
+		self.functions.push(NativeFn::typed(name, func));
+		self
+	}
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+	/// The map the module is defined as
+	pub fn to_literal(&self) -> Literal {
+		let functions = self
+			.functions
+			.iter()
+			.map(|function| (function.name(), Literal::NativeFunction(function.clone())))
+			.collect::<BTreeMap<_, _>>();
+		Literal::Map(Shared::new(functions))
+	}
+}
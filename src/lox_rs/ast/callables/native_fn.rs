@@ -1,21 +1,43 @@
-use std::fmt;
+use std::{fmt, rc::Rc};
 
 use anyhow::Result;
 
 use super::{
 	super::{
-		super::ast::visitors::interp::Interperter,
-		expr::{Expr, Literal},
+		super::{
+			ast::visitors::interp::Interperter,
+			convert::{FromLox, IntoLox},
+			error::{ErrorKind, Exit, Interrupted, LoxError, RuntimeError, Thrown},
+		},
+		expr::Literal,
 	},
-	callable::Callable,
+	callable::{Arity, Callable},
 };
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// What a native runs. It gets the interpreter so it can call back into Lox.
+pub type NativeFunc = dyn Fn(&mut Interperter, Vec<Literal>) -> Result<Literal>;
+
+#[derive(Clone)]
 pub struct NativeFn {
-	name: &'static str,
-	arity: usize,
-	func: fn(Vec<Literal>) -> Result<Literal>,
+	name: Rc<str>,
+	arity: Arity,
+	func: Rc<NativeFunc>,
+}
+// Closures can't be printed or compared, so natives are compared by identity
+impl fmt::Debug for NativeFn {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("NativeFn")
+			.field("name", &self.name)
+			.field("arity", &self.arity)
+			.finish_non_exhaustive()
+	}
 }
+impl PartialEq for NativeFn {
+	fn eq(&self, other: &Self) -> bool {
+		self.name == other.name && self.arity == other.arity && Rc::ptr_eq(&self.func, &other.func)
+	}
+}
+impl Eq for NativeFn {}
 impl fmt::Display for NativeFn {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		let Self { name, .. } = self;
@@ -23,27 +45,86 @@ impl fmt::Display for NativeFn {
 	}
 }
 impl Callable for NativeFn {
-	fn arity(&self) -> usize {
-		self.arity
+	fn name(&self) -> String {
+		self.name.to_string()
 	}
-	fn call(&self, interp: &mut Interperter, args: Vec<Expr>) -> Result<Literal> {
-		let args: Vec<Literal> = args.iter().map(|a| interp.expr(a).unwrap()).collect();
-		(self.func)(args)
+	fn arity(&self) -> Arity {
+		self.arity
 	}
-	fn to_string(&self) -> String {
-		format!("{self}")
+	fn call(&self, interp: &mut Interperter, args: Vec<Literal>) -> Result<Literal> {
+		(self.func)(interp, args).map_err(|error| {
+			// Errors from Lox code the native called back into carry on as they are
+			if error.is::<LoxError>()
+				|| error.is::<RuntimeError>()
+				|| error.is::<Thrown>()
+				|| error.is::<Exit>()
+				|| error.is::<Interrupted>()
+			{
+				error
+			} else {
+				ErrorKind::Native.error(error).into()
+			}
+		})
 	}
 }
 impl NativeFn {
-	pub const fn new(
-		name: &'static str,
-		arity: usize,
-		func: fn(Vec<Literal>) -> Result<Literal>,
+	pub fn new(
+		name: impl Into<Rc<str>>,
+		arity: impl Into<Arity>,
+		func: impl Fn(&mut Interperter, Vec<Literal>) -> Result<Literal> + 'static,
 	) -> Self {
-		Self { name, arity, func }
+		Self {
+			name: name.into(),
+			arity: arity.into(),
+			func: Rc::new(func),
+		}
 	}
-
-	pub fn get_name(&self) -> String {
-		self.name.to_string()
+	/// A native for a plain Rust function, like `|n: f64, s: String| -> Result<bool>`, that
+	/// converts its arguments from Lox and its result back
+	pub fn typed<Args>(name: impl Into<Rc<str>>, func: impl TypedNative<Args> + 'static) -> Self {
+		let name: Rc<str> = name.into();
+		Self::new(name.clone(), func.arity(), move |_, args| {
+			func.call_with(&name, args)
+		})
 	}
 }
+
+/// Rust functions whose arguments all convert [`FromLox`] and whose result converts [`IntoLox`].
+/// `Args` is the tuple of argument types, only there to tell the implementations apart.
+pub trait TypedNative<Args> {
+	fn arity(&self) -> usize;
+	/// Convert the arguments and call the function. The arity was already checked.
+	fn call_with(&self, name: &str, args: Vec<Literal>) -> Result<Literal>;
+}
+macro_rules! typed_native {
+	($arity:literal; $($arg:ident),*) => {
+		impl<Func, Ret, $($arg),*> TypedNative<($($arg,)*)> for Func
+		where
+			Func: Fn($($arg),*) -> Result<Ret>,
+			Ret: IntoLox,
+			$($arg: FromLox,)*
+		{
+			fn arity(&self) -> usize {
+				$arity
+			}
+			#[allow(non_snake_case, unused_mut, unused_variables)]
+			fn call_with(&self, name: &str, args: Vec<Literal>) -> Result<Literal> {
+				let mut args = args.into_iter().enumerate();
+				$(
+					let (i, arg) = args.next().unwrap_or_default();
+					let $arg = $arg::from_lox(arg).map_err(|error| {
+						ErrorKind::of(&error).error(format!("Argument {} of `{name}`: {error}", i + 1))
+					})?;
+				)*
+				Ok(self($($arg),*)?.into_lox())
+			}
+		}
+	};
+}
+typed_native!(0;);
+typed_native!(1; A);
+typed_native!(2; A, B);
+typed_native!(3; A, B, C);
+typed_native!(4; A, B, C, D);
+typed_native!(5; A, B, C, D, E);
+typed_native!(6; A, B, C, D, E, F);
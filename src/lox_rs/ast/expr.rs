@@ -1,18 +1,29 @@
-use std::fmt;
+use std::{cell::Cell, collections::BTreeMap, fmt, mem, ops::Deref, rc::Rc};
+
+use anyhow::{bail, Result};
 
 use super::{
-	super::lexer::tokens::token_type::{Operator, Punctuation},
+	super::{
+		error::ErrorKind,
+		host::Host,
+		lexer::tokens::{token::Span, token_type::Operator},
+		stack,
+	},
 	callables::{custom_fn::CustomFn, native_fn::NativeFn},
-	visitors::parens::parenthesize,
+	pattern::MatchArm,
+	visitors::parens::{parenthesize, parenthesize_target},
 };
 
 #[derive(Debug, Default, Clone, PartialEq)]
 pub enum Literal {
 	Number(f64),
-	String(String),
+	String(Shared<String>),
 	Boolean(bool),
+	List(Shared<Vec<Literal>>),
+	Map(Shared<BTreeMap<String, Literal>>),
 	NativeFunction(NativeFn),
 	CustomFunction(CustomFn),
+	HostObject(Host),
 	#[default]
 	Null,
 }
@@ -22,8 +33,11 @@ impl Literal {
 			Self::Number(n) => *n != 0.,
 			Self::String(s) => !s.is_empty(),
 			Self::Boolean(b) => *b,
+			Self::List(items) => !items.is_empty(),
+			Self::Map(entries) => !entries.is_empty(),
 			Literal::CustomFunction(_) => true,
 			Literal::NativeFunction(_) => true,
+			Literal::HostObject(_) => true,
 			Self::Null => false,
 		}
 	}
@@ -34,23 +48,270 @@ impl fmt::Display for Literal {
 			Literal::Number(n) => write!(f, "{n}"),
 			Literal::String(s) => write!(f, "\"{s}\""),
 			Literal::Boolean(b) => write!(f, "{b}"),
+			Literal::List(items) => stack::grow(|| {
+				let items = items.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+				write!(f, "[{}]", items.join(", "))
+			}),
+			Literal::Map(entries) => stack::grow(|| {
+				let entries = entries
+					.iter()
+					.map(|(key, value)| format!("\"{key}\": {value}"))
+					.collect::<Vec<_>>();
+				write!(f, "{{{}}}", entries.join(", "))
+			}),
 			Literal::CustomFunction(func) => write!(f, "{func}"),
 			Literal::NativeFunction(func) => write!(f, "{func}"),
+			Literal::HostObject(host) => write!(f, "{host}"),
 			Literal::Null => write!(f, "null"),
 		}
 	}
 }
 
+/// How many bytes of heap the strings, lists and maps an interpreter made are taking up. They
+/// count themselves here when they're made and take themselves off when they're dropped.
+#[derive(Debug, Clone, Default)]
+pub struct Heap(Rc<Cell<usize>>);
+impl Heap {
+	pub fn in_use(&self) -> usize {
+		self.0.get()
+	}
+	/// Make sure `size` more bytes fit in `limit` before they're taken up
+	pub fn reserve(&self, size: usize, limit: Option<usize>) -> Result<()> {
+		match limit {
+			Some(limit) if self.in_use().saturating_add(size) > limit => {
+				bail!(ErrorKind::OutOfMemory.error(format!(
+					"Out of memory: {size} more bytes would take the {} in use over the limit of \
+					 {limit}",
+					self.in_use()
+				)))
+			}
+			_ => Ok(()),
+		}
+	}
+	fn add(&self, size: usize) {
+		self.0.set(self.0.get().saturating_add(size));
+	}
+	fn remove(&self, size: usize) {
+		self.0.set(self.0.get().saturating_sub(size));
+	}
+}
+
+/// Roughly how many bytes of heap the contents of a [`Shared`] take up themselves. The values in
+/// lists and maps are shared, so they count on their own.
+pub trait HeapSize {
+	fn heap_size(&self) -> usize;
+}
+impl HeapSize for String {
+	fn heap_size(&self) -> usize {
+		self.len()
+	}
+}
+impl HeapSize for Vec<Literal> {
+	fn heap_size(&self) -> usize {
+		self.len() * mem::size_of::<Literal>()
+	}
+}
+impl HeapSize for BTreeMap<String, Literal> {
+	fn heap_size(&self) -> usize {
+		self.keys().map(|key| entry_size(key)).sum()
+	}
+}
+/// What an entry of a map takes up, see [`HeapSize`]
+pub fn entry_size(key: &str) -> usize {
+	mem::size_of::<(String, Literal)>() + key.len()
+}
+
+/// A string, or the items of a list or the entries of a map, shared until one of the values
+/// holding them is changed so reading a variable doesn't copy the whole thing. Programs can nest
+/// them as deeply as they like, so dropping, comparing and debug printing them make room on the
+/// stack first.
+pub struct Shared<T: Default>(Rc<Counted<T>>);
+/// The contents of a [`Shared`], and the [`Heap`] they count towards if an interpreter made them
+struct Counted<T> {
+	value: T,
+	heap: Option<Heap>,
+	size: usize,
+}
+// Copying the contents before they're changed takes up as much heap again
+impl<T: Clone> Clone for Counted<T> {
+	fn clone(&self) -> Self {
+		if let Some(heap) = &self.heap {
+			heap.add(self.size);
+		}
+		Self {
+			value: self.value.clone(),
+			heap: self.heap.clone(),
+			size: self.size,
+		}
+	}
+}
+impl<T> Drop for Counted<T> {
+	fn drop(&mut self) {
+		if let Some(heap) = &self.heap {
+			heap.remove(self.size);
+		}
+	}
+}
+impl<T: Default> Shared<T> {
+	/// Contents that don't count towards any heap, like the ones hosts and natives make
+	pub fn new(value: T) -> Self {
+		Self(Rc::new(Counted {
+			value,
+			heap: None,
+			size: 0,
+		}))
+	}
+	/// Contents that count towards `heap` for as long as they're around
+	pub fn counted(value: T, heap: &Heap) -> Self
+	where
+		T: HeapSize,
+	{
+		let size = value.heap_size();
+		heap.add(size);
+		Self(Rc::new(Counted {
+			value,
+			heap: Some(heap.clone()),
+			size,
+		}))
+	}
+	/// How much heap [`Shared::make_mut`] takes up by copying the contents, if they're shared
+	pub fn copy_size(this: &Self) -> usize {
+		if Rc::strong_count(&this.0) > 1 {
+			this.0.size
+		} else {
+			0
+		}
+	}
+	/// The contents to change, copied first if anything else is sharing them
+	pub fn make_mut(this: &mut Self) -> &mut T
+	where
+		T: Clone,
+	{
+		&mut Rc::make_mut(&mut this.0).value
+	}
+	/// Count `size` more bytes for contents that grew, once nothing else is sharing them
+	pub fn grow(this: &mut Self, size: usize) {
+		if let Some(counted) = Rc::get_mut(&mut this.0) {
+			counted.size += size;
+			if let Some(heap) = &counted.heap {
+				heap.add(size);
+			}
+		}
+	}
+	/// The contents, copied if anything else is sharing them
+	pub fn unwrap_or_clone(mut this: Self) -> T
+	where
+		T: Clone,
+	{
+		match Rc::get_mut(&mut this.0) {
+			Some(counted) => mem::take(&mut counted.value),
+			None => this.0.value.clone(),
+		}
+	}
+}
+impl<T: Default> Clone for Shared<T> {
+	fn clone(&self) -> Self {
+		Self(Rc::clone(&self.0))
+	}
+}
+impl<T: Default> Deref for Shared<T> {
+	type Target = T;
+	fn deref(&self) -> &T {
+		&self.0.value
+	}
+}
+impl<T: Default + PartialEq> PartialEq for Shared<T> {
+	fn eq(&self, other: &Self) -> bool {
+		stack::grow(|| self.0.value == other.0.value)
+	}
+}
+impl<T: Default + fmt::Debug> fmt::Debug for Shared<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		stack::grow(|| self.0.value.fmt(f))
+	}
+}
+impl<T: Default + fmt::Display> fmt::Display for Shared<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.0.value.fmt(f)
+	}
+}
+impl<T: Default> Drop for Shared<T> {
+	fn drop(&mut self) {
+		// Only the last one to go drops the contents, which can hold more of these
+		if let Some(counted) = Rc::get_mut(&mut self.0) {
+			let value = mem::take(&mut counted.value);
+			stack::grow(|| drop(value));
+		}
+	}
+}
+
+/// A variable being read or written, with the scope it lives in once it's been resolved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Var {
+	pub name: String,
+	/// How many scopes up from the current one it was declared, or `None` if it's a global
+	pub depth: Option<usize>,
+	/// Its index among the locals of that scope, unused for globals
+	pub slot: usize,
+}
+impl Var {
+	pub fn new(name: impl Into<String>) -> Self {
+		Self {
+			name: name.into(),
+			depth: None,
+			slot: 0,
+		}
+	}
+}
+impl fmt::Display for Var {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.name)
+	}
+}
+
+/// Somewhere a value can be assigned to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+	Variable(Var),
+	/// An item of a list or an entry of a map
+	Index(Box<Expr>, Box<Expr>),
+	/// An entry of a map or a property of a host object
+	Property(Box<Expr>, String),
+}
+impl fmt::Display for Target {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", &parenthesize_target(self))
+	}
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
-	Assign(String, Box<Expr>),
+	Assign(Target, Box<Expr>),
+	CompoundAssign(Target, Operator, Box<Expr>),
+	Prefix(Operator, Target),
+	Postfix(Target, Operator),
 	Binary(Box<Expr>, Operator, Box<Expr>),
-	Call(Box<Expr>, Punctuation, Vec<Expr>),
+	/// The span is where the arguments start, to point at the call in tracebacks
+	Call(Box<Expr>, Span, Vec<Expr>),
 	Grouping(Box<Expr>),
 	Literal(Literal),
 	Unary(Operator, Box<Expr>),
-	Variable(String),
+	Variable(Var),
 	Logical(Box<Expr>, Operator, Box<Expr>),
+	Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+	Match(Box<Expr>, Vec<MatchArm>),
+	List(Vec<Expr>),
+	Map(Vec<(String, Expr)>),
+	Index(Box<Expr>, Box<Expr>),
+	/// A property of the object. With `?.` (optional) a `null` object skips the rest of the
+	/// chain, which comes out as `null`.
+	Get(Box<Expr>, String, bool),
+}
+// The tree is as deep as the program is nested
+impl Drop for Expr {
+	fn drop(&mut self) {
+		stack::drop_with_room(self, || Expr::Literal(Literal::Null));
+	}
 }
 impl fmt::Display for Expr {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1,4 +1,5 @@
 pub mod callables;
 pub mod expr;
+pub mod pattern;
 pub mod stmt;
 pub mod visitors;
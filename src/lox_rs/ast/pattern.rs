@@ -0,0 +1,94 @@
+use std::fmt;
+
+use super::{
+	callables::callable::Arity,
+	expr::{Expr, Literal},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+	/// `_`, matches anything without binding it
+	Wildcard,
+	/// Matches anything and binds it to a name
+	Binding(String),
+	/// Matches values equal to the literal
+	Literal(Literal),
+	/// `[a, b = 1, ...rest]`, matches a list element by element
+	List(Vec<PatternElement>, Option<String>),
+	/// `{x, y: alias = 1}`, matches a map that has (or has defaults for) the given keys
+	Map(Vec<(String, PatternElement)>),
+}
+impl fmt::Display for Pattern {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Wildcard => write!(f, "_"),
+			Self::Binding(name) => write!(f, "{name}"),
+			Self::Literal(lit) => write!(f, "{lit}"),
+			Self::List(elements, rest) => {
+				let mut parts = elements.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+				if let Some(rest) = rest {
+					parts.push(format!("...{rest}"));
+				}
+				write!(f, "[{}]", parts.join(", "))
+			}
+			Self::Map(entries) => {
+				let parts = entries
+					.iter()
+					.map(|(key, element)| format!("{key}: {element}"))
+					.collect::<Vec<_>>();
+				write!(f, "{{{}}}", parts.join(", "))
+			}
+		}
+	}
+}
+
+/// A nested pattern, with the value to fall back on when there's nothing to match it against
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternElement {
+	pub pattern: Pattern,
+	pub default: Option<Expr>,
+}
+impl fmt::Display for PatternElement {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.default {
+			Some(default) => write!(f, "{} = {default}", self.pattern),
+			None => write!(f, "{}", self.pattern),
+		}
+	}
+}
+
+/// A function's parameter list, `(a, [b, c], d = 1, ...rest)`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Params {
+	pub elements: Vec<PatternElement>,
+	pub rest: Option<String>,
+}
+impl Params {
+	/// Parameters with a default are optional, and `...rest` takes any number of extra arguments
+	pub fn arity(&self) -> Arity {
+		Arity {
+			min: self.elements.iter().filter(|e| e.default.is_none()).count(),
+			max: self.rest.is_none().then_some(self.elements.len()),
+		}
+	}
+}
+impl fmt::Display for Params {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut parts = self
+			.elements
+			.iter()
+			.map(|e| e.to_string())
+			.collect::<Vec<_>>();
+		if let Some(rest) = &self.rest {
+			parts.push(format!("...{rest}"));
+		}
+		write!(f, "{}", parts.join(", "))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+	pub pattern: Pattern,
+	pub guard: Option<Expr>,
+	pub body: Expr,
+}
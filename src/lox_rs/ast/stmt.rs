@@ -1,7 +1,11 @@
+use std::rc::Rc;
+
 use anyhow::Result;
 
 use super::{
+	super::{error::RuntimeError, stack},
 	expr::{Expr, Literal},
+	pattern::{Params, Pattern},
 	visitors::interp::Interperter,
 };
 
@@ -10,15 +14,28 @@ pub enum Stmt {
 	Expression(Expr),
 	Print(Expr),
 	Var(String, Option<Expr>),
+	Destructure(Pattern, Expr),
 	Block(Vec<Stmt>),
 	If(Expr, Box<Stmt>, Option<Box<Stmt>>),
 	While(Expr, Box<Stmt>),
 	For(Option<Box<Stmt>>, Option<Expr>, Option<Expr>, Box<Stmt>),
-	Function(String, Vec<Expr>, Box<Stmt>),
+	/// The body is shared with every function the declaration makes
+	Function(String, Params, Rc<Stmt>),
 	Return(Expr),
+	Break,
+	Continue,
+	Throw(Expr),
+	/// `try` block, the `catch` variable and block, and the `finally` block
+	Try(Box<Stmt>, Option<(String, Box<Stmt>)>, Option<Box<Stmt>>),
 }
 impl Stmt {
-	pub fn interpret(&self, interp: &mut Interperter) -> Result<Literal> {
-		interp.exec(self)
+	pub fn interpret(&self, interp: &mut Interperter) -> Result<Literal, RuntimeError> {
+		interp.exec(self).map_err(RuntimeError::from)
+	}
+}
+// Blocks nest as deeply as the program does
+impl Drop for Stmt {
+	fn drop(&mut self) {
+		stack::drop_with_room(self, || Stmt::Break);
 	}
 }
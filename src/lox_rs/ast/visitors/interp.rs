@@ -1,87 +1,366 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	mem,
+	rc::Rc,
+	sync::{Arc, Weak},
+	time::Instant,
+};
+
 use anyhow::{bail, Result};
 
 use super::super::{
 	super::{
 		ast::{
-			expr::{Expr, Literal},
+			expr::{entry_size, Expr, Heap, HeapSize, Literal, Shared, Target, Var},
+			pattern::{MatchArm, Pattern},
 			stmt::Stmt,
 		},
-		env::Env,
+		env::{Env, SharedEnv, WeakEnv},
+		error::{ErrorKind, Exit, Frame, Interrupted, RuntimeError, Thrown},
 		lexer::tokens::token_type::Operator,
+		output::Output,
+		stack,
+	},
+	callables::{
+		builtins::builtins,
+		callable::Callable,
+		custom_fn::CustomFn,
+		module::NativeModule,
+		native_fn::NativeFn,
 	},
-	callables::{builtins::NOW, callable::Callable, custom_fn::CustomFn},
 };
 
+/// How many calls can be nested by default
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+/// How many bytes a single string, list or map can take up by default
+pub const DEFAULT_MEMORY_LIMIT: usize = 256 << 20;
+/// Reading the clock costs more than most steps, so the deadline is only checked this often
+const STEPS_PER_DEADLINE_CHECK: u32 = 1024;
+
+/// Where an assignment writes to, with every expression in its target already evaluated
+enum Place<'t> {
+	Variable(&'t Var),
+	/// An item of the list or map in another place
+	Index(Box<Place<'t>>, Literal),
+	/// A property of the map or host object in another place
+	Property(Box<Place<'t>>, &'t str),
+	/// A value that isn't stored anywhere, like the result of a call
+	Value(Literal),
+}
+
+/// How a statement finished, anything but `Normal` unwinds until something handles it
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlFlow {
+	/// Ran to the end, with the value of the last thing it evaluated
+	Normal(Literal),
+	/// Unwinds to the function call
+	Return(Literal),
+	/// Unwinds to the enclosing loop
+	Break,
+	Continue,
+}
+
 #[derive(Debug)]
 pub struct Interperter {
-	pub global: Env,
-	pub local: Env,
-	return_value: Option<Literal>,
+	/// Natives and top level declarations. They're looked up by name when they're used, so a
+	/// function can refer to globals that are only declared after it
+	pub global: SharedEnv,
+	/// The innermost scope, `global` itself while running top level code
+	pub local: SharedEnv,
+	/// The scopes functions were declared in, besides the global one. A function can be stored
+	/// somewhere its scope reaches and keep it alive, so they're emptied when the interpreter
+	/// is dropped to free them.
+	closed_over: Vec<WeakEnv>,
+	/// What the strings, lists and maps the program made take up, see [`Self::memory_limit`]
+	heap: Heap,
+	/// Values that were thrown and haven't been caught yet, see [`Thrown`]
+	thrown: HashMap<usize, (Weak<()>, Literal)>,
+	next_thrown: usize,
+	/// How many calls can be nested before the program fails with a `StackOverflowError`
+	pub max_call_depth: usize,
+	call_depth: usize,
+	/// How many more statements and expressions can be evaluated, as many as it takes if `None`
+	pub fuel: Option<u64>,
+	/// When to stop the program, however much fuel it has left
+	pub deadline: Option<Instant>,
+	/// Steps since the deadline was last checked
+	steps: u32,
+	/// How many bytes of heap the strings, lists and maps the program made can take up in all.
+	/// What hosts, natives and `catch` make doesn't count, and neither do scopes and the call stack.
+	pub memory_limit: Option<usize>,
+	/// Where `print` and warnings go
+	pub output: Output,
 }
 impl Interperter {
+	/// Bitwise operators only work on numbers that can be represented as an `i64`
+	fn to_integer(n: f64, op: &Operator) -> Result<i64> {
+		// `i64::MAX` rounds up to 2^63 as an `f64`, which doesn't fit, so that's the bound
+		if n.fract() != 0. || !n.is_finite() || n < i64::MIN as f64 || n >= -(i64::MIN as f64) {
+			bail!(ErrorKind::Value.error(format!(
+				"Please only apply `{}` to integral numbers, not {}",
+				op.to_str(),
+				n
+			)));
+		}
+		Ok(n as i64)
+	}
+	/// Match `value` against `pattern`, defining its bindings in the local scope along the way
+	pub fn bind_pattern(&mut self, pattern: &Pattern, value: Literal) -> Result<bool> {
+		match pattern {
+			Pattern::Wildcard => Ok(true),
+			Pattern::Binding(name) => {
+				self.local.borrow_mut().define(name.to_string(), value);
+				Ok(true)
+			}
+			Pattern::Literal(lit) => Ok(lit == &value),
+			Pattern::List(elements, rest) => {
+				let Literal::List(items) = value else {
+					return Ok(false);
+				};
+				if rest.is_none() && items.len() > elements.len() {
+					return Ok(false);
+				}
+
+				let mut items = Shared::unwrap_or_clone(items).into_iter();
+				for element in elements {
+					let item = match (items.next(), &element.default) {
+						(Some(item), _) => item,
+						(None, Some(default)) => self.expr(default)?,
+						(None, None) => return Ok(false),
+					};
+					if !self.bind_pattern(&element.pattern, item)? {
+						return Ok(false);
+					}
+				}
+				if let Some(rest) = rest {
+					let rest_items = self.list(items.collect())?;
+					self.local.borrow_mut().define(rest.to_string(), rest_items);
+				}
+				Ok(true)
+			}
+			Pattern::Map(entries) => {
+				let Literal::Map(map) = value else {
+					return Ok(false);
+				};
+				let mut map = Shared::unwrap_or_clone(map);
+
+				for (key, element) in entries {
+					let value = match (map.remove(key), &element.default) {
+						(Some(value), _) => value,
+						(None, Some(default)) => self.expr(default)?,
+						(None, None) => return Ok(false),
+					};
+					if !self.bind_pattern(&element.pattern, value)? {
+						return Ok(false);
+					}
+				}
+				Ok(true)
+			}
+		}
+	}
+	/// Make sure `size` more bytes fit in the memory limit before they're taken up
+	fn allocate(&self, size: usize) -> Result<()> {
+		self.heap.reserve(size, self.memory_limit)
+	}
+	/// A string of `size` bytes that `build` fills in. Room for it is made up front, so it fails
+	/// with an `OutOfMemoryError` instead of aborting when there isn't enough memory.
+	fn string(&self, size: usize, build: impl FnOnce(&mut String)) -> Result<Literal> {
+		self.allocate(size)?;
+		let mut s = String::new();
+		if s.try_reserve_exact(size).is_err() {
+			bail!(ErrorKind::OutOfMemory
+				.error(format!("Out of memory: couldn't allocate {size} bytes")));
+		}
+		build(&mut s);
+
+		Ok(Literal::String(Shared::counted(s, &self.heap)))
+	}
+	/// A list that counts towards the memory limit
+	pub fn list(&self, items: Vec<Literal>) -> Result<Literal> {
+		self.allocate(items.heap_size())?;
+		Ok(Literal::List(Shared::counted(items, &self.heap)))
+	}
+	/// A map that counts towards the memory limit
+	pub fn map(&self, entries: BTreeMap<String, Literal>) -> Result<Literal> {
+		self.allocate(entries.heap_size())?;
+		Ok(Literal::Map(Shared::counted(entries, &self.heap)))
+	}
+	/// How many bytes the strings, lists and maps the program made are taking up
+	pub fn memory_in_use(&self) -> usize {
+		self.heap.in_use()
+	}
+	fn binary(&self, left: Literal, op: &Operator, right: Literal) -> Result<Literal> {
+		let new_lit = match op {
+			Operator::NotEq => Literal::Boolean(left != right),
+			Operator::EqEq => Literal::Boolean(left == right),
+			Operator::Gt => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Boolean(n1 > n2),
+				other => bail!(ErrorKind::Type
+					.error(format!("Please only compair numbers, but got {:?}", &other))),
+			},
+			Operator::Gte => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Boolean(n1 >= n2),
+				other => bail!(ErrorKind::Type
+					.error(format!("Please only compair numbers, but got {:?}", &other))),
+			},
+			Operator::Lt => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Boolean(n1 < n2),
+				other => bail!(ErrorKind::Type
+					.error(format!("Please only compair numbers, but got {:?}", &other))),
+			},
+			Operator::Lte => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Boolean(n1 <= n2),
+				other => bail!(ErrorKind::Type
+					.error(format!("Please only compair numbers, but got {:?}", &other))),
+			},
+			Operator::Add => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Number(n1 + n2),
+				(Literal::String(s1), Literal::String(s2)) => {
+					self.string(s1.len().saturating_add(s2.len()), |s| {
+						s.push_str(&s1);
+						s.push_str(&s2);
+					})?
+				}
+				other => bail!(ErrorKind::Type.error(format!(
+					"Please only add number to number or string to string, not {:?}",
+					&other
+				))),
+			},
+			Operator::Sub => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Number(n1 - n2),
+				other => bail!(ErrorKind::Type.error(format!(
+					"Please only subtract a number from a number, not {:?}",
+					&other
+				))),
+			},
+			Operator::Mul => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Number(n1 * n2),
+				(Literal::Number(n), Literal::String(s))
+				| (Literal::String(s), Literal::Number(n)) => {
+					let times = n.round() as usize;
+					self.string(s.len().saturating_mul(times), |repeated| {
+						if !s.is_empty() {
+							for _ in 0..times {
+								repeated.push_str(&s);
+							}
+						}
+					})?
+				}
+				other => bail!(ErrorKind::Type.error(format!(
+					"Please only multiply number to number and string to number, not {:?}",
+					&other
+				))),
+			},
+			Operator::Div => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Number(n1 / n2),
+				other => bail!(ErrorKind::Type.error(format!(
+					"Please only divide a number from a number, not {:?}",
+					&other
+				))),
+			},
+			Operator::Mod => match (left, right) {
+				(Literal::Number(_), Literal::Number(0.)) => {
+					bail!(ErrorKind::Value.error("Can't take the remainder of a division by zero"))
+				}
+				// Floored modulo - the result has the sign of the divisor (like `//`). `%` is exact,
+				// so it's worked out from that rather than from the quotient.
+				(Literal::Number(n1), Literal::Number(n2)) => {
+					let r = n1 % n2;
+					if r != 0. && (r < 0.) != (n2 < 0.) {
+						Literal::Number(r + n2)
+					} else {
+						Literal::Number(r)
+					}
+				}
+				other => bail!(ErrorKind::Type.error(format!(
+					"Please only take the remainder of a number by a number, not {:?}",
+					&other
+				))),
+			},
+			Operator::FloorDiv => match (left, right) {
+				(Literal::Number(_), Literal::Number(0.)) => {
+					bail!(ErrorKind::Value.error("Can't integer-divide by zero"))
+				}
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Number((n1 / n2).floor()),
+				other => bail!(ErrorKind::Type.error(format!(
+					"Please only integer-divide a number by a number, not {:?}",
+					&other
+				))),
+			},
+			Operator::Pow => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => Literal::Number(n1.powf(n2)),
+				other => bail!(ErrorKind::Type.error(format!(
+					"Please only raise a number to a number's power, not {:?}",
+					&other
+				))),
+			},
+			Operator::BitAnd
+			| Operator::BitOr
+			| Operator::BitXor
+			| Operator::ShiftLeft
+			| Operator::ShiftRight => match (left, right) {
+				(Literal::Number(n1), Literal::Number(n2)) => {
+					let n1 = Self::to_integer(n1, op)?;
+					let n2 = Self::to_integer(n2, op)?;
+
+					let result = match op {
+						Operator::BitAnd => n1 & n2,
+						Operator::BitOr => n1 | n2,
+						Operator::BitXor => n1 ^ n2,
+						Operator::ShiftLeft | Operator::ShiftRight => {
+							let Ok(n2 @ 0..=63) = u32::try_from(n2) else {
+								bail!(ErrorKind::Value.error(format!("Can't shift by {} bits", n2)));
+							};
+							if let Operator::ShiftLeft = op {
+								n1 << n2
+							} else {
+								n1 >> n2
+							}
+						}
+						_ => unreachable!(),
+					};
+					Literal::Number(result as f64)
+				}
+				other => bail!(ErrorKind::Type.error(format!(
+					"Please only apply `{}` to numbers, not {:?}",
+					op.to_str(),
+					&other
+				))),
+			},
+			other => bail!("Should not get {:?} as an binary op", &other),
+		};
+		Ok(new_lit)
+	}
+	/// Use up a step of the budget, or stop the program if there's none left
+	fn tick(&mut self) -> Result<()> {
+		if let Some(fuel) = &mut self.fuel {
+			if *fuel == 0 {
+				bail!(Interrupted::OutOfFuel);
+			}
+			*fuel -= 1;
+		}
+		if let Some(deadline) = self.deadline {
+			self.steps += 1;
+			if self.steps >= STEPS_PER_DEADLINE_CHECK {
+				self.steps = 0;
+				if Instant::now() >= deadline {
+					bail!(Interrupted::DeadlineExceeded);
+				}
+			}
+		}
+		Ok(())
+	}
 	pub fn expr(&mut self, expr: &Expr) -> Result<Literal> {
+		self.tick()?;
+		stack::grow(|| self.eval(expr))
+	}
+	fn eval(&mut self, expr: &Expr) -> Result<Literal> {
 		match expr {
 			Expr::Binary(left, op, right) => {
 				let left = self.expr(left)?;
 				let right = self.expr(right)?;
 
-				let new_lit = match op {
-					Operator::NotEq => Literal::Boolean(left != right),
-					Operator::EqEq => Literal::Boolean(left == right),
-					Operator::Gt => match (left, right) {
-						(Literal::Number(n1), Literal::Number(n2)) => Literal::Boolean(n1 > n2),
-						other => bail!("Please only compair numbers, but got {:?}", &other),
-					},
-					Operator::Gte => match (left, right) {
-						(Literal::Number(n1), Literal::Number(n2)) => Literal::Boolean(n1 >= n2),
-						other => bail!("Please only compair numbers, but got {:?}", &other),
-					},
-					Operator::Lt => match (left, right) {
-						(Literal::Number(n1), Literal::Number(n2)) => Literal::Boolean(n1 < n2),
-						other => bail!("Please only compair numbers, but got {:?}", &other),
-					},
-					Operator::Lte => match (left, right) {
-						(Literal::Number(n1), Literal::Number(n2)) => Literal::Boolean(n1 <= n2),
-						other => bail!("Please only compair numbers, but got {:?}", &other),
-					},
-					Operator::Add => match (left, right) {
-						(Literal::Number(n1), Literal::Number(n2)) => Literal::Number(n1 + n2),
-						(Literal::String(s1), Literal::String(s2)) => Literal::String(s1 + &s2),
-						other => bail!(
-							"Please only add number to number or string to string, not {:?}",
-							&other
-						),
-					},
-					Operator::Sub => match (left, right) {
-						(Literal::Number(n1), Literal::Number(n2)) => Literal::Number(n1 - n2),
-						other => bail!(
-							"Please only subtract a number from a number, not {:?}",
-							&other
-						),
-					},
-					Operator::Mul => match (left, right) {
-						(Literal::Number(n1), Literal::Number(n2)) => Literal::Number(n1 * n2),
-						(Literal::Number(n), Literal::String(s)) => {
-							Literal::String(s.repeat(n.round() as usize))
-						}
-						(Literal::String(s), Literal::Number(n)) => {
-							Literal::String(s.repeat(n.round() as usize))
-						}
-						other => bail!(
-							"Please only multiply number to number and string to number, not {:?}",
-							&other
-						),
-					},
-					Operator::Div => match (left, right) {
-						(Literal::Number(n1), Literal::Number(n2)) => Literal::Number(n1 / n2),
-						other => bail!(
-							"Please only divide a number from a number, not {:?}",
-							&other
-						),
-					},
-					other => bail!("Should not get {:?} as an binary op", &other),
-				};
-				Ok(new_lit)
+				self.binary(left, op, right)
 			}
 			Expr::Grouping(expr) => self.expr(expr),
 			Expr::Literal(lit) => Ok(lit.clone()),
@@ -104,11 +383,17 @@ impl Interperter {
 						Literal::Boolean(b) => Literal::Number(if b { 1. } else { 0. }),
 						Literal::Null => Literal::Number(0.),
 						Literal::NativeFunction(_) | Literal::CustomFunction(_) => {
-							bail!("Can't add a function")
+							bail!(ErrorKind::Type.error("Can't add a function"))
+						}
+						Literal::HostObject(_) => {
+							bail!(ErrorKind::Type.error("Can't add a host object"))
+						}
+						Literal::List(_) | Literal::Map(_) => {
+							bail!(ErrorKind::Type.error("Can't add a collection"))
 						}
 					},
 					Operator::Sub => match right {
-						Literal::Number(n) => Literal::Number(n * (-1.)),
+						Literal::Number(n) => Literal::Number(-n),
 						Literal::String(s) => {
 							if s.is_empty() {
 								Literal::Number(0.)
@@ -122,7 +407,13 @@ impl Interperter {
 						Literal::Boolean(b) => Literal::Number(if b { -1. } else { 0. }),
 						Literal::Null => Literal::Number(0.),
 						Literal::NativeFunction(_) | Literal::CustomFunction(_) => {
-							bail!("Can't sub a function")
+							bail!(ErrorKind::Type.error("Can't sub a function"))
+						}
+						Literal::HostObject(_) => {
+							bail!(ErrorKind::Type.error("Can't sub a host object"))
+						}
+						Literal::List(_) | Literal::Map(_) => {
+							bail!(ErrorKind::Type.error("Can't sub a collection"))
 						}
 					},
 					Operator::Not => match right {
@@ -130,25 +421,65 @@ impl Interperter {
 						Literal::String(s) => Literal::Boolean(!s.is_empty()),
 						Literal::Boolean(b) => Literal::Boolean(!b),
 						Literal::Null => Literal::Boolean(true),
-						Literal::NativeFunction(_) | Literal::CustomFunction(_) => {
-							Literal::Boolean(false)
-						}
+						Literal::NativeFunction(_)
+						| Literal::CustomFunction(_)
+						| Literal::HostObject(_) => Literal::Boolean(false),
+						Literal::List(items) => Literal::Boolean(items.is_empty()),
+						Literal::Map(entries) => Literal::Boolean(entries.is_empty()),
+					},
+					Operator::BitNot => match right {
+						Literal::Number(n) => Literal::Number(!Self::to_integer(n, op)? as f64),
+						other => bail!(ErrorKind::Type.error(format!(
+							"Please only apply `~` to a number, not {:?}",
+							&other
+						))),
 					},
 					other => bail!("Should not get {:?} as an unary operator", &other),
 				};
 				Ok(new_lit)
 			}
-			Expr::Variable(name) => self
-				.local
-				.get(name.clone())
-				.or_else(|_| self.global.get(name.to_string()))
-				.cloned(),
-			Expr::Assign(name, value) => {
+			Expr::Variable(var) => self.lookup(var),
+			Expr::Assign(target, value) => {
+				let place = self.place(target)?;
 				let value = self.expr(value)?;
-				self.local.redefine(name.to_string(), value.clone())?;
+				self.write(&place, value.clone())?;
 
 				Ok(value)
 			}
+			Expr::CompoundAssign(target, op, value) => {
+				let Some(bin_op) = op.to_binary() else {
+					bail!("Invalid compound assignment operator recieved {:?}", op);
+				};
+				let place = self.place(target)?;
+				let current = self.read(&place)?;
+				let value = self.expr(value)?;
+				let value = self.binary(current, &bin_op, value)?;
+				self.write(&place, value.clone())?;
+
+				Ok(value)
+			}
+			Expr::Prefix(op, target) | Expr::Postfix(target, op) => {
+				let Some(bin_op) = op.to_binary() else {
+					bail!("Invalid update operator recieved {:?}", op);
+				};
+				let place = self.place(target)?;
+				let current = self.read(&place)?;
+				if !matches!(current, Literal::Number(_)) {
+					bail!(ErrorKind::Type.error(format!(
+						"Please only apply `{}` to a number, not {:?}",
+						op.to_str(),
+						&current
+					)));
+				}
+				let value = self.binary(current.clone(), &bin_op, Literal::Number(1.))?;
+				self.write(&place, value.clone())?;
+
+				if let Expr::Prefix(..) = expr {
+					Ok(value)
+				} else {
+					Ok(current)
+				}
+			}
 			Expr::Logical(lhs, op, rhs) => {
 				let lhs = self.expr(lhs)?;
 				match (op, lhs.is_truthy()) {
@@ -156,148 +487,573 @@ impl Interperter {
 					(Operator::Or, false) => self.expr(rhs),
 					(Operator::And, true) => self.expr(rhs),
 					(Operator::And, false) => Ok(lhs),
+					(Operator::Coalesce, _) => {
+						if lhs == Literal::Null {
+							self.expr(rhs)
+						} else {
+							Ok(lhs)
+						}
+					}
 					(other, _) => bail!("Invalid logical operator recieved {:?}", other),
 				}
 			}
-			Expr::Call(callee, _paren, args) => {
-				let callee = self.expr(callee)?;
-				let args = args.to_vec();
+			Expr::Conditional(condition, then_expr, else_expr) => {
+				if self.expr(condition)?.is_truthy() {
+					self.expr(then_expr)
+				} else {
+					self.expr(else_expr)
+				}
+			}
+			Expr::Match(value, arms) => {
+				let value = self.expr(value)?;
+
+				for MatchArm {
+					pattern,
+					guard,
+					body,
+				} in arms
+				{
+					let result = self.scoped(|interp| {
+						if !interp.bind_pattern(pattern, value.clone())? {
+							return Ok(None);
+						}
+						if let Some(guard) = guard {
+							if !interp.expr(guard)?.is_truthy() {
+								return Ok(None);
+							}
+						}
+						interp.expr(body).map(Some)
+					})?;
 
-				match &callee {
-					Literal::NativeFunction(func) => func.call(self, args),
-					Literal::CustomFunction(func) => func.call(self, args),
-					other => bail!("Unexpected type for the callee, {:?}", other),
+					if let Some(result) = result {
+						return Ok(result);
+					}
 				}
+				bail!(ErrorKind::Value.error(format!("No `match` arm matched the value {}", &value)))
+			}
+			Expr::List(items) => {
+				let items = items
+					.iter()
+					.map(|item| self.expr(item))
+					.collect::<Result<Vec<_>>>()?;
+				self.list(items)
+			}
+			Expr::Map(entries) => {
+				let mut map = BTreeMap::new();
+				for (key, value) in entries {
+					map.insert(key.to_string(), self.expr(value)?);
+				}
+				self.map(map)
+			}
+			// A `?.` on `null` skips the rest of the chain it's in, which comes out as `null`
+			Expr::Get(..) | Expr::Index(..) | Expr::Call(..) => {
+				Ok(self.link(expr)?.unwrap_or_default())
 			}
 		}
 	}
-	fn stmt(&mut self, stmt: &Stmt) -> Result<Literal> {
-		match stmt {
-			Stmt::Expression(e) => self.expr(e),
+	/// Evaluate a link of a chain of property accesses, indexes and calls, `None` if a `?.`
+	/// before it found `null`
+	fn chain(&mut self, expr: &Expr) -> Result<Option<Literal>> {
+		self.tick()?;
+		stack::grow(|| self.link(expr))
+	}
+	fn link(&mut self, expr: &Expr) -> Result<Option<Literal>> {
+		match expr {
+			Expr::Get(object, name, optional) => {
+				let Some(object) = self.chain(object)? else {
+					return Ok(None);
+				};
+				if *optional && object == Literal::Null {
+					return Ok(None);
+				}
+
+				Self::property(&object, name).map(Some)
+			}
+			Expr::Index(target, index) => {
+				let Some(target) = self.chain(target)? else {
+					return Ok(None);
+				};
+				let index = self.expr(index)?;
+
+				Self::index(&target, &index).map(Some)
+			}
+			Expr::Call(callee, span, args) => {
+				let Some(callee) = self.chain(callee)? else {
+					return Ok(None);
+				};
+				let args = args
+					.iter()
+					.map(|arg| self.expr(arg))
+					.collect::<Result<Vec<_>>>()?;
+
+				let callee = Self::callable(&callee)?;
+				callee.arity().check(&callee.name(), args.len())?;
+
+				self.invoke(callee, args)
+					.map(Some)
+					.map_err(|error| RuntimeError::unwind(error, callee.name(), *span))
+			}
+			other => self.eval(other).map(Some),
+		}
+	}
+	fn index(target: &Literal, index: &Literal) -> Result<Literal> {
+		match (target, index) {
+			(Literal::List(items), Literal::Number(n)) => {
+				Ok(items[Self::list_index(items, *n)?].clone())
+			}
+			(Literal::Map(entries), Literal::String(key)) => {
+				Ok(entries.get(key.as_str()).cloned().unwrap_or_default())
+			}
+			other => bail!(ErrorKind::Type.error(format!("Can't index into {:?}", &other))),
+		}
+	}
+	fn list_index(items: &[Literal], n: f64) -> Result<usize> {
+		if n.fract() != 0. || n < 0. || n >= items.len() as f64 {
+			bail!(ErrorKind::Index.error(format!(
+				"Index {n} is out of bounds for {}",
+				Literal::List(Shared::new(items.to_vec()))
+			)));
+		}
+		Ok(n as usize)
+	}
+	/// Evaluate what an assignment writes to, before the value that's assigned
+	fn place<'t>(&mut self, target: &'t Target) -> Result<Place<'t>> {
+		match target {
+			Target::Variable(var) => Ok(Place::Variable(var)),
+			Target::Index(target, index) => {
+				let target = self.container(target)?;
+				let index = self.expr(index)?;
+				Ok(Place::Index(Box::new(target), index))
+			}
+			Target::Property(object, name) => {
+				let object = self.container(object)?;
+				Ok(Place::Property(Box::new(object), name))
+			}
+		}
+	}
+	/// The place the value `expr` evaluates to is in
+	fn container<'t>(&mut self, expr: &'t Expr) -> Result<Place<'t>> {
+		match expr {
+			Expr::Variable(var) => Ok(Place::Variable(var)),
+			Expr::Index(target, index) => {
+				let target = stack::grow(|| self.container(target))?;
+				let index = self.expr(index)?;
+				Ok(Place::Index(Box::new(target), index))
+			}
+			Expr::Get(object, name, false) => {
+				let object = stack::grow(|| self.container(object))?;
+				Ok(Place::Property(Box::new(object), name))
+			}
+			other => self.expr(other).map(Place::Value),
+		}
+	}
+	fn read(&mut self, place: &Place) -> Result<Literal> {
+		match place {
+			Place::Variable(var) => self.lookup(var),
+			Place::Index(container, index) => {
+				let container = stack::grow(|| self.read(container))?;
+				Self::index(&container, index)
+			}
+			Place::Property(object, name) => {
+				let object = stack::grow(|| self.read(object))?;
+				Self::property(&object, name)
+			}
+			Place::Value(value) => Ok(value.clone()),
+		}
+	}
+	fn write(&mut self, place: &Place, value: Literal) -> Result<()> {
+		match place {
+			Place::Variable(var) => return self.assign(var, value),
+			// Host objects are shared rather than copied, so setting the property is all it
+			// takes, without reading it first
+			Place::Property(object, name) => {
+				if let Literal::HostObject(host) = self.read(object)? {
+					return host.set(name, value);
+				}
+			}
+			_ => {}
+		}
+		let mut value = Some(value);
+		self.update(place, &mut |slot| {
+			*slot = value.take().unwrap_or_default();
+			Ok(())
+		})
+	}
+	/// Change the value in a place, writing it back through everything that contains it
+	fn update(
+		&mut self,
+		place: &Place,
+		f: &mut dyn FnMut(&mut Literal) -> Result<()>,
+	) -> Result<()> {
+		match place {
+			Place::Variable(var) => {
+				// Taken out of the variable while it's changed, since a list or map that's
+				// shared would be copied first
+				let mut value = self.lookup(var)?;
+				self.assign(var, Literal::Null)?;
+				let result = f(&mut value);
+				self.assign(var, value)?;
+				result
+			}
+			Place::Index(container, index) => stack::grow(|| {
+				let (heap, limit) = (self.heap.clone(), self.memory_limit);
+				self.update(container, &mut |container| match (container, index) {
+					(Literal::List(items), Literal::Number(n)) => {
+						let i = Self::list_index(items, *n)?;
+						f(&mut Self::make_mut(items, &heap, limit)?[i])
+					}
+					(Literal::Map(entries), Literal::String(key)) => {
+						Self::update_entry(entries, key, f, &heap, limit)
+					}
+					other => bail!(ErrorKind::Type.error(format!("Can't index into {:?}", &other))),
+				})
+			}),
+			Place::Property(object, name) => stack::grow(|| {
+				let (heap, limit) = (self.heap.clone(), self.memory_limit);
+				self.update(object, &mut |object| match object {
+					Literal::Map(entries) => Self::update_entry(entries, name, f, &heap, limit),
+					Literal::HostObject(host) => {
+						let mut value = host.get(name)?;
+						f(&mut value)?;
+						host.set(name, value)
+					}
+					other => bail!(ErrorKind::Type.error(format!(
+						"Only maps and host objects have properties, not {other}"
+					))),
+				})
+			}),
+			Place::Value(value) => f(&mut value.clone()),
+		}
+	}
+	/// The contents of a list or map to change, once copying them fits in the memory limit
+	fn make_mut<'v, T: Default + Clone>(
+		shared: &'v mut Shared<T>,
+		heap: &Heap,
+		limit: Option<usize>,
+	) -> Result<&'v mut T> {
+		heap.reserve(Shared::copy_size(shared), limit)?;
+		Ok(Shared::make_mut(shared))
+	}
+	fn update_entry(
+		entries: &mut Shared<BTreeMap<String, Literal>>,
+		key: &str,
+		f: &mut dyn FnMut(&mut Literal) -> Result<()>,
+		heap: &Heap,
+		limit: Option<usize>,
+	) -> Result<()> {
+		let map = Self::make_mut(entries, heap, limit)?;
+		if let Some(entry) = map.get_mut(key) {
+			return f(entry);
+		}
+		// A new entry is only added if the assignment goes through
+		heap.reserve(entry_size(key), limit)?;
+		let mut entry = Literal::Null;
+		f(&mut entry)?;
+		map.insert(key.to_string(), entry);
+		Shared::grow(entries, entry_size(key));
+		Ok(())
+	}
+	fn property(object: &Literal, name: &str) -> Result<Literal> {
+		match object {
+			Literal::Map(entries) => Ok(entries.get(name).cloned().unwrap_or_default()),
+			Literal::HostObject(host) => host.get(name),
+			other => bail!(ErrorKind::Type.error(format!(
+				"Only maps and host objects have properties, not {other}"
+			))),
+		}
+	}
+	fn stmt(&mut self, stmt: &Stmt) -> Result<ControlFlow> {
+		self.tick()?;
+		stack::grow(|| self.execute(stmt))
+	}
+	fn execute(&mut self, stmt: &Stmt) -> Result<ControlFlow> {
+		let value = match stmt {
+			Stmt::Expression(e) => self.expr(e)?,
 			Stmt::Print(e) => {
 				let result = self.expr(e)?;
-				println!("{}", &result);
+				self.output.print(&result)?;
 
-				Ok(result)
+				result
 			}
 			Stmt::Var(name, value) => {
-				if let Some(value) = value {
-					let value = self.expr(value)?;
-					self.local.define(name.to_string(), value.clone());
+				let value = match value {
+					Some(value) => self.expr(value)?,
+					None => Literal::Null,
+				};
+				self.local
+					.borrow_mut()
+					.define(name.to_string(), value.clone());
 
-					Ok(value)
-				} else {
-					self.local.define(name.to_string(), Literal::Null);
-					Ok(Literal::Null)
+				value
+			}
+			Stmt::Destructure(pattern, value) => {
+				let value = self.expr(value)?;
+				if !self.bind_pattern(pattern, value.clone())? {
+					bail!(ErrorKind::Value
+						.error(format!("Can't destructure {} into {}", &value, pattern)));
 				}
+
+				value
 			}
 			Stmt::Block(statements) => {
-				let prev_env = self.local.clone();
-				self.local = Env::new(Box::new(prev_env));
-
-				let mut result = Literal::Null;
-				for statement in statements {
-					if let Some(lit) = &self.return_value {
-						let lit = lit.clone();
-						self.return_value = None;
-
-						return Ok(lit);
-					} else {
-						result = self.stmt(statement)?;
+				return self.scoped(|interp| {
+					let mut result = Literal::Null;
+					for statement in statements {
+						match interp.stmt(statement)? {
+							ControlFlow::Normal(value) => result = value,
+							unwind => return Ok(unwind),
+						}
 					}
-				}
-				self.local = *self.local.get_parent().unwrap();
-
-				Ok(result)
+					Ok(ControlFlow::Normal(result))
+				})
 			}
 			Stmt::If(cond, then_block, else_block) => {
-				let mut result = Literal::Null;
 				if self.expr(cond)?.is_truthy() {
-					result = self.stmt(then_block)?;
+					return self.stmt(then_block);
 				} else if let Some(else_block) = else_block {
-					result = self.stmt(else_block)?;
+					return self.stmt(else_block);
 				}
-				Ok(result)
+				Literal::Null
 			}
 			Stmt::While(cond, block) => {
 				let mut result = Literal::Null;
 				while self.expr(cond)?.is_truthy() {
-					result = self.stmt(block)?;
+					match self.stmt(block)? {
+						ControlFlow::Normal(value) => result = value,
+						ControlFlow::Continue => {}
+						ControlFlow::Break => break,
+						unwind @ ControlFlow::Return(_) => return Ok(unwind),
+					}
 				}
-				Ok(result)
+				result
 			}
 			Stmt::For(initializer, condition, increment, block) => {
-				let mut init_param_name: Option<String> = None;
-				if let Some(initializer) = initializer {
-					if let Stmt::Var(ref name, _) = **initializer {
-						init_param_name = Some(name.clone());
-						self.exec(initializer)?;
-					} else {
-						bail!("This should be a loop interator initializer")
+				return self.scoped(|interp| {
+					if let Some(initializer) = initializer {
+						interp.stmt(initializer)?;
 					}
-				}
-
-				let block = Box::new(Stmt::Block({
-					let mut stmts: Vec<Stmt> = Vec::new();
 
-					stmts.push(*block.clone());
-					if let Some(increment) = increment {
-						stmts.push(Stmt::Expression(increment.clone()));
+					let mut result = Literal::Null;
+					while condition
+						.as_ref()
+						.map_or(Ok(Literal::Boolean(true)), |expr| interp.expr(expr))?
+						.is_truthy()
+					{
+						match interp.stmt(block)? {
+							ControlFlow::Normal(value) => result = value,
+							ControlFlow::Continue => {}
+							ControlFlow::Break => break,
+							unwind @ ControlFlow::Return(_) => return Ok(unwind),
+						}
+						if let Some(increment) = increment {
+							interp.expr(increment)?;
+						}
 					}
-					stmts
-				}));
-				let mut result = Literal::Null;
-				while condition
-					.clone()
-					.map_or_else(|| Ok(Literal::Null), |expr| self.expr(&expr))?
-					.is_truthy()
-				{
-					let block = block.clone();
-					result = self.stmt(&block)?;
-				}
-				if let Some(init_param_name) = init_param_name {
-					self.local.remove(&init_param_name);
-				}
-
-				Ok(result)
+					Ok(ControlFlow::Normal(result))
+				})
 			}
 			Stmt::Function(name, inputs, block) => {
-				let custom_fn = Literal::CustomFunction(CustomFn::new(
+				let custom_fn = CustomFn::new(
 					name.to_string(),
-					inputs.to_vec(),
+					inputs.clone(),
 					block.clone(),
-					Some(Box::new(self.local.clone())),
-				));
-				self.local.define(name.clone(), custom_fn.clone());
+					self.local.clone(),
+				);
+				self.close_over_local();
+				self.local
+					.borrow_mut()
+					.define(name.clone(), Literal::CustomFunction(custom_fn.downgrade()));
 
-				Ok(custom_fn)
+				Literal::CustomFunction(custom_fn)
 			}
-			Stmt::Return(value) => {
-				let ret_val = self.expr(value)?;
-				self.return_value = Some(ret_val.clone());
+			Stmt::Return(value) => return Ok(ControlFlow::Return(self.expr(value)?)),
+			Stmt::Throw(value) => {
+				let value = self.expr(value)?;
+				let thrown = Thrown {
+					id: self.next_thrown,
+					description: value.to_string(),
+					alive: Arc::new(()),
+				};
+				self.next_thrown += 1;
+				// Whatever was thrown before and dropped without being caught can go
+				self.thrown.retain(|_, (alive, _)| alive.strong_count() > 0);
+				self.thrown
+					.insert(thrown.id, (Arc::downgrade(&thrown.alive), value));
 
-				Ok(ret_val)
+				bail!(thrown)
 			}
+			Stmt::Try(body, catch, finally) => {
+				let result = match (self.stmt(body), catch) {
+					(Err(error), Some((name, handler))) => match self.catch(error) {
+						Ok(caught) => self.scoped(|interp| {
+							interp.local.borrow_mut().define(name.to_string(), caught);
+							interp.stmt(handler)
+						}),
+						Err(error) => Err(error),
+					},
+					(result, _) => result,
+				};
+
+				// A `finally` that unwinds itself wins over however the rest finished
+				if let Some(finally) = finally {
+					match self.stmt(finally)? {
+						ControlFlow::Normal(_) => {}
+						unwind => return Ok(unwind),
+					}
+				}
+				return result;
+			}
+			Stmt::Break => return Ok(ControlFlow::Break),
+			Stmt::Continue => return Ok(ControlFlow::Continue),
+		};
+		Ok(ControlFlow::Normal(value))
+	}
+
+	/// Read a variable from the scope the resolver placed it in
+	fn lookup(&self, var: &Var) -> Result<Literal> {
+		let Some(depth) = var.depth else {
+			return self.global.borrow().get(&var.name);
+		};
+		let Some(value) = self.local.borrow().get_at(depth, var.slot) else {
+			bail!(ErrorKind::Name.error(format!("Undefined variable `{}`", var.name)));
+		};
+		Ok(value)
+	}
+	fn assign(&self, var: &Var, value: Literal) -> Result<()> {
+		let Some(depth) = var.depth else {
+			return self
+				.global
+				.borrow_mut()
+				.redefine(var.name.to_string(), value);
+		};
+		let assigned = self.local.borrow_mut().redefine_at(depth, var.slot, value);
+		if assigned.is_none() {
+			bail!(ErrorKind::Name.error(format!("{} was not initiated yet", var.name)));
 		}
+		Ok(())
 	}
 
-	pub fn exec(&mut self, stmt: &Stmt) -> Result<Literal> {
-		if let Some(lit) = &self.return_value {
-			let lit = lit.clone();
-			self.return_value = None;
+	/// Turn an error into the value a `catch` block receives, unless it's an `exit` or the
+	/// program was [`Interrupted`].
+	/// Thrown values are caught as they are, and errors become a map of their `kind`, `message`
+	/// and the `trace` of calls they unwound out of.
+	fn catch(&mut self, error: anyhow::Error) -> Result<Literal> {
+		let RuntimeError { error, trace } = RuntimeError::from(error);
+		if error.is::<Exit>() || error.is::<Interrupted>() {
+			return Err(RuntimeError { error, trace }.into());
+		}
+		if let Some(Thrown { id, .. }) = error.downcast_ref::<Thrown>() {
+			if let Some((_, value)) = self.thrown.remove(id) {
+				return Ok(value);
+			}
+		}
+
+		// Not counted against the memory limit, so running out of memory can always be caught
+		let trace = trace
+			.into_iter()
+			.map(|Frame { function, span }| {
+				Literal::Map(Shared::new(BTreeMap::from([
+					(
+						"function".to_string(),
+						Literal::String(Shared::new(function)),
+					),
+					("line".to_string(), Literal::Number(span.line as f64)),
+					("column".to_string(), Literal::Number(span.column as f64)),
+				])))
+			})
+			.collect();
+		Ok(Literal::Map(Shared::new(BTreeMap::from([
+			(
+				"kind".to_string(),
+				Literal::String(Shared::new(ErrorKind::of(&error).name().to_string())),
+			),
+			(
+				"message".to_string(),
+				Literal::String(Shared::new(error.to_string())),
+			),
+			("trace".to_string(), Literal::List(Shared::new(trace))),
+		]))))
+	}
+	/// Define a native as a global
+	pub fn register_native(&mut self, native: NativeFn) {
+		self.global
+			.borrow_mut()
+			.define(native.name(), Literal::NativeFunction(native));
+	}
+	/// Define a global map of the module's natives, under its name
+	pub fn register_module(&mut self, module: &NativeModule) {
+		self.global
+			.borrow_mut()
+			.define(module.name().to_string(), module.to_literal());
+	}
+	/// Call a function from outside of any Lox code, with arguments that are already evaluated
+	pub fn call(&mut self, callee: &Literal, args: Vec<Literal>) -> Result<Literal> {
+		let callee = Self::callable(callee)?;
+		callee.arity().check(&callee.name(), args.len())?;
+
+		self.invoke(callee, args)
+	}
+	fn invoke(&mut self, callee: &dyn Callable, args: Vec<Literal>) -> Result<Literal> {
+		if self.call_depth >= self.max_call_depth {
+			bail!(ErrorKind::StackOverflow.error(format!(
+				"Stack overflow: more than {} nested calls",
+				self.max_call_depth
+			)));
+		}
+
+		self.call_depth += 1;
+		let result = callee.call(self, args);
+		self.call_depth -= 1;
 
-			Ok(lit)
-		} else {
-			self.stmt(stmt)
+		result
+	}
+	fn callable(callee: &Literal) -> Result<&dyn Callable> {
+		match callee {
+			Literal::NativeFunction(func) => Ok(func),
+			Literal::CustomFunction(func) => Ok(func),
+			other => {
+				bail!(ErrorKind::Type.error(format!("Unexpected type for the callee, {:?}", other)))
+			}
+		}
+	}
+	pub fn exec(&mut self, stmt: &Stmt) -> Result<Literal> {
+		match self.stmt(stmt)? {
+			ControlFlow::Normal(value) | ControlFlow::Return(value) => Ok(value),
+			ControlFlow::Break | ControlFlow::Continue => {
+				bail!("Can't `break` or `continue` outside of a loop")
+			}
 		}
 	}
-	pub fn exec_block(&mut self, block: &Stmt, env: Box<Env>) -> Result<Literal> {
-		let prev_env = self.local.clone();
-		self.local = Env::new(env);
+	/// Run `block` with `env` as its local scope
+	pub fn exec_block(&mut self, block: &Stmt, env: SharedEnv) -> Result<Literal> {
+		let prev_env = std::mem::replace(&mut self.local, env);
 
 		let result = self.exec(block);
 		self.local = prev_env;
 
+		result
+	}
+	/// Remember that a function was declared in the local scope, see [`Self::closed_over`]
+	fn close_over_local(&mut self) {
+		let scope = &self.local;
+		let seen = |other: &WeakEnv| other.as_ptr() == Rc::as_ptr(scope);
+		if Rc::ptr_eq(scope, &self.global) || self.closed_over.last().is_some_and(seen) {
+			return;
+		}
+		// Forget the scopes that are gone before making room for more
+		if self.closed_over.len() == self.closed_over.capacity() {
+			self.closed_over.retain(|scope| scope.strong_count() > 0);
+		}
+		self.closed_over.push(Rc::downgrade(scope));
+	}
+	/// Run `f` in a new scope nested in the current one
+	fn scoped<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+		let env = Env::new(self.local.clone()).shared();
+		let prev_env = std::mem::replace(&mut self.local, env);
+
+		let result = f(self);
+		self.local = prev_env;
+
 		result
 	}
 }
@@ -306,13 +1062,37 @@ impl Default for Interperter {
 	fn default() -> Self {
 		let global = {
 			let mut g = Env::default();
-			g.define(NOW.get_name(), Literal::NativeFunction(NOW));
+			for native in builtins() {
+				g.define(native.name(), Literal::NativeFunction(native));
+			}
 			g
 		};
+		// Top level declarations are globals, everything nested in them gets its own scope
+		let global = global.shared();
 		Self {
+			local: global.clone(),
 			global,
-			local: Env::default(),
-			return_value: None,
+			closed_over: Vec::new(),
+			heap: Heap::default(),
+			thrown: HashMap::new(),
+			next_thrown: 0,
+			max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+			call_depth: 0,
+			fuel: None,
+			deadline: None,
+			steps: 0,
+			memory_limit: Some(DEFAULT_MEMORY_LIMIT),
+			output: Output::default(),
+		}
+	}
+}
+impl Drop for Interperter {
+	fn drop(&mut self) {
+		// Whatever emptying a scope drops is dropped after the scope is let go of
+		let scopes = self.closed_over.iter().filter_map(|scope| scope.upgrade());
+		for scope in scopes.chain([self.global.clone()]) {
+			let env = mem::take(&mut *scope.borrow_mut());
+			drop(env);
 		}
 	}
 }
@@ -1,2 +1,6 @@
 pub mod interp;
 pub mod parens;
+pub mod resolver;
+
+#[cfg(test)]
+mod tests;
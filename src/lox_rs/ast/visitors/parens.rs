@@ -1,6 +1,13 @@
-use super::super::expr::Expr;
+use super::super::{
+	super::stack,
+	expr::{Expr, Target},
+	pattern::MatchArm,
+};
 
 pub fn parenthesize(expr: &Expr) -> String {
+	stack::grow(|| parenthesize_expr(expr))
+}
+fn parenthesize_expr(expr: &Expr) -> String {
 	match expr {
 		Expr::Binary(left, op, right) => {
 			let left_str = &parenthesize(left);
@@ -19,9 +26,22 @@ pub fn parenthesize(expr: &Expr) -> String {
 			format!("({op} {right_str})")
 		}
 		Expr::Variable(name) => format!("(var {name})"),
-		Expr::Assign(var_name, value) => {
+		Expr::Assign(target, value) => {
+			let value = &parenthesize(value);
+			format!("(assign {target} {value})")
+		}
+		Expr::CompoundAssign(target, op, value) => {
 			let value = &parenthesize(value);
-			format!("(assign {var_name} {value})")
+			let op = op.to_str();
+			format!("({op} {target} {value})")
+		}
+		Expr::Prefix(op, target) => {
+			let op = op.to_str();
+			format!("({op} {target})")
+		}
+		Expr::Postfix(target, op) => {
+			let op = op.to_str();
+			format!("({target} {op})")
 		}
 		Expr::Logical(expr_1, op, expr_2) => {
 			let expr_1 = &parenthesize(expr_1);
@@ -29,6 +49,78 @@ pub fn parenthesize(expr: &Expr) -> String {
 			let op = op.to_str();
 			format!("({op} {expr_1} {expr_2})")
 		}
-		Expr::Call(callee, _paren, args) => format!("({callee}, {args:?})"),
+		Expr::Call(callee, _paren, args) => {
+			let callee = &parenthesize(callee);
+			let args = args
+				.iter()
+				.map(|arg| format!(" {}", parenthesize(arg)))
+				.collect::<String>();
+			format!("(call {callee}{args})")
+		}
+		Expr::Conditional(condition, then_expr, else_expr) => {
+			let condition = &parenthesize(condition);
+			let then_expr = &parenthesize(then_expr);
+			let else_expr = &parenthesize(else_expr);
+			format!("(? {condition} {then_expr} {else_expr})")
+		}
+		Expr::Match(value, arms) => {
+			let value = &parenthesize(value);
+			let arms = arms
+				.iter()
+				.map(|arm| {
+					let MatchArm {
+						pattern,
+						guard,
+						body,
+					} = arm;
+					let guard = guard
+						.as_ref()
+						.map(|guard| format!(" if {}", parenthesize(guard)))
+						.unwrap_or_default();
+					let body = parenthesize(body);
+					format!(" ({pattern}{guard} => {body})")
+				})
+				.collect::<String>();
+			format!("(match {value}{arms})")
+		}
+		Expr::List(items) => {
+			let items = items
+				.iter()
+				.map(|item| format!(" {}", parenthesize(item)))
+				.collect::<String>();
+			format!("(list{items})")
+		}
+		Expr::Map(entries) => {
+			let entries = entries
+				.iter()
+				.map(|(key, value)| format!(" ({key} {})", parenthesize(value)))
+				.collect::<String>();
+			format!("(map{entries})")
+		}
+		Expr::Index(target, index) => {
+			let target = &parenthesize(target);
+			let index = &parenthesize(index);
+			format!("(index {target} {index})")
+		}
+		Expr::Get(object, name, optional) => {
+			let object = &parenthesize(object);
+			let op = if *optional { "?." } else { "." };
+			format!("({op} {object} {name})")
+		}
+	}
+}
+
+pub fn parenthesize_target(target: &Target) -> String {
+	match target {
+		Target::Variable(var) => var.to_string(),
+		Target::Index(target, index) => {
+			let target = &parenthesize(target);
+			let index = &parenthesize(index);
+			format!("(index {target} {index})")
+		}
+		Target::Property(object, name) => {
+			let object = &parenthesize(object);
+			format!("(. {object} {name})")
+		}
 	}
 }
@@ -0,0 +1,418 @@
+use std::{
+	collections::{HashMap, HashSet},
+	rc::Rc,
+};
+
+use super::super::{
+	super::stack,
+	callables::callable::Arity,
+	expr::{Expr, Target, Var},
+	pattern::{MatchArm, Params, Pattern, PatternElement},
+	stmt::Stmt,
+};
+
+/// A local the resolver has seen declared
+struct Local {
+	slot: usize,
+	/// Whether its initializer has finished
+	defined: bool,
+	/// Index into [`Resolver::declarations`]
+	declaration: usize,
+}
+
+/// Any variable, along with its arity if it's a function that is never reassigned
+#[derive(Default)]
+struct Declaration {
+	arity: Option<Arity>,
+	reassigned: bool,
+}
+
+/// A call to a variable, to check against its arity once the whole program has been seen
+struct Call {
+	name: String,
+	/// `None` for a global, which is looked up by name when it's called
+	declaration: Option<usize>,
+	args: usize,
+}
+
+/// Works out which scope every variable refers to before anything runs, mirroring the scopes
+/// [`super::interp::Interperter`] creates. Top level declarations don't get slots, anything that
+/// isn't found in a local scope is looked up in the globals.
+///
+/// It also checks calls against the parameters of the function they refer to. Calls whose
+/// callee can't be known statically are left to the check the interpreter makes on every call.
+#[derive(Default)]
+struct Resolver {
+	/// Local scopes, innermost last
+	scopes: Vec<HashMap<String, Local>>,
+	declarations: Vec<Declaration>,
+	/// The latest declaration of every global
+	globals: HashMap<String, usize>,
+	/// Globals that are assigned to somewhere, which could be before they're declared
+	reassigned_globals: HashSet<String>,
+	calls: Vec<Call>,
+	/// How many functions the resolver is currently inside of
+	functions: usize,
+	/// How many loops the resolver is inside of, within the current function
+	loops: usize,
+	errors: Vec<String>,
+}
+impl Resolver {
+	fn declare(&mut self, name: &str) -> usize {
+		self.declarations.push(Declaration::default());
+		let declaration = self.declarations.len() - 1;
+
+		let Some(scope) = self.scopes.last_mut() else {
+			// Globals are looked up when they're used, so declaring one again changes what
+			// every use of it refers to, like assigning to it would
+			if self.globals.insert(name.to_string(), declaration).is_some() {
+				self.reassigned_globals.insert(name.to_string());
+			}
+			return declaration;
+		};
+		// Locals get their slots in the same order the interpreter defines them in
+		let local = Local {
+			slot: scope.len(),
+			defined: false,
+			declaration,
+		};
+		if scope.insert(name.to_string(), local).is_some() {
+			self.errors
+				.push(format!("Already a variable named `{name}` in this scope"));
+		}
+		declaration
+	}
+	fn define(&mut self, name: &str) {
+		if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(name)) {
+			local.defined = true;
+		}
+	}
+	/// Define a name bound by a pattern, declaring it first unless that was done up front
+	fn bind(&mut self, name: &str, declared: bool) {
+		if !declared {
+			self.declare(name);
+		}
+		self.define(name);
+	}
+	fn lookup(&self, name: &str) -> Option<(usize, &Local)> {
+		self.scopes
+			.iter()
+			.rev()
+			.enumerate()
+			.find_map(|(depth, scope)| Some((depth, scope.get(name)?)))
+	}
+	fn resolve(&mut self, var: &mut Var) {
+		if let Some((depth, local)) = self.lookup(&var.name) {
+			var.depth = Some(depth);
+			var.slot = local.slot;
+		}
+	}
+	fn reassign(&mut self, var: &mut Var) {
+		self.resolve(var);
+		match self.lookup(&var.name).map(|(_, local)| local.declaration) {
+			Some(declaration) => self.declarations[declaration].reassigned = true,
+			None => {
+				self.reassigned_globals.insert(var.name.clone());
+			}
+		}
+	}
+	fn call(&mut self, name: &str, args: usize) {
+		let declaration = match self.lookup(name) {
+			Some((_, local)) => Some(local.declaration),
+			// Functions can call globals declared after them, top level code can't
+			None if self.functions > 0 || self.globals.contains_key(name) => None,
+			None => return,
+		};
+		self.calls.push(Call {
+			name: name.to_string(),
+			declaration,
+			args,
+		});
+	}
+	/// The calls that will fail the arity check
+	fn arity_errors(&self) -> Vec<String> {
+		self.calls
+			.iter()
+			.filter_map(
+				|Call {
+				     name,
+				     declaration,
+				     args,
+				 }| {
+					let declaration = match declaration {
+						Some(declaration) => *declaration,
+						None if self.reassigned_globals.contains(name) => return None,
+						None => *self.globals.get(name)?,
+					};
+					let Declaration { arity, reassigned } = &self.declarations[declaration];
+					match arity {
+						Some(arity) if !reassigned => arity.check(name, *args).err(),
+						_ => None,
+					}
+				},
+			)
+			.map(|e| e.to_string())
+			.collect()
+	}
+	fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+		self.scopes.push(HashMap::new());
+		f(self);
+		self.scopes.pop();
+	}
+
+	/// Declare the names a pattern binds without defining them yet
+	fn declare_pattern(&mut self, pattern: &Pattern) {
+		match pattern {
+			Pattern::Wildcard | Pattern::Literal(_) => {}
+			Pattern::Binding(name) => {
+				self.declare(name);
+			}
+			Pattern::List(elements, rest) => {
+				for element in elements {
+					self.declare_pattern(&element.pattern);
+				}
+				if let Some(rest) = rest {
+					self.declare(rest);
+				}
+			}
+			Pattern::Map(entries) => {
+				for (_, element) in entries {
+					self.declare_pattern(&element.pattern);
+				}
+			}
+		}
+	}
+	/// Resolve the defaults and bind the names of a pattern, in the order they're bound at runtime
+	fn pattern(&mut self, pattern: &mut Pattern, declared: bool) {
+		match pattern {
+			Pattern::Wildcard | Pattern::Literal(_) => {}
+			Pattern::Binding(name) => self.bind(name, declared),
+			Pattern::List(elements, rest) => {
+				for element in elements {
+					self.pattern_element(element, declared);
+				}
+				if let Some(rest) = rest {
+					self.bind(rest, declared);
+				}
+			}
+			Pattern::Map(entries) => {
+				for (_, element) in entries {
+					self.pattern_element(element, declared);
+				}
+			}
+		}
+	}
+	fn pattern_element(&mut self, element: &mut PatternElement, declared: bool) {
+		if let Some(default) = &mut element.default {
+			self.expr(default);
+		}
+		self.pattern(&mut element.pattern, declared);
+	}
+	fn function(&mut self, params: &mut Params, body: &mut Stmt) {
+		self.functions += 1;
+		// A function body can't `break` out of the loop it's declared in
+		let loops = std::mem::take(&mut self.loops);
+		self.scoped(|resolver| {
+			for element in &mut params.elements {
+				resolver.pattern_element(element, false);
+			}
+			if let Some(rest) = &params.rest {
+				resolver.bind(rest, false);
+			}
+			resolver.stmt(body);
+		});
+		self.loops = loops;
+		self.functions -= 1;
+	}
+	fn target(&mut self, target: &mut Target) {
+		match target {
+			Target::Variable(var) => self.reassign(var),
+			Target::Index(target, index) => {
+				self.expr(target);
+				self.expr(index);
+			}
+			Target::Property(object, _) => self.expr(object),
+		}
+	}
+	fn expr(&mut self, expr: &mut Expr) {
+		stack::grow(|| self.resolve_expr(expr))
+	}
+	fn resolve_expr(&mut self, expr: &mut Expr) {
+		match expr {
+			Expr::Variable(var) => {
+				let in_initializer = self
+					.scopes
+					.last()
+					.and_then(|scope| scope.get(&var.name))
+					.is_some_and(|local| !local.defined);
+				if in_initializer {
+					self.errors.push(format!(
+						"Can't read local variable `{}` in its own initializer",
+						var.name
+					));
+				}
+				self.resolve(var);
+			}
+			Expr::Assign(target, value) | Expr::CompoundAssign(target, _, value) => {
+				self.target(target);
+				self.expr(value);
+			}
+			Expr::Prefix(_, target) | Expr::Postfix(target, _) => self.target(target),
+			Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => {
+				self.expr(left);
+				self.expr(right);
+			}
+			Expr::Call(callee, _, args) => {
+				if let Expr::Variable(var) = callee.as_ref() {
+					self.call(&var.name, args.len());
+				}
+				self.expr(callee);
+				for arg in args {
+					self.expr(arg);
+				}
+			}
+			Expr::Grouping(expr) | Expr::Unary(_, expr) => self.expr(expr),
+			Expr::Literal(_) => {}
+			Expr::Conditional(condition, then_expr, else_expr) => {
+				self.expr(condition);
+				self.expr(then_expr);
+				self.expr(else_expr);
+			}
+			Expr::Match(value, arms) => {
+				self.expr(value);
+				for MatchArm {
+					pattern,
+					guard,
+					body,
+				} in arms
+				{
+					self.scoped(|resolver| {
+						resolver.pattern(pattern, false);
+						if let Some(guard) = guard {
+							resolver.expr(guard);
+						}
+						resolver.expr(body);
+					});
+				}
+			}
+			Expr::List(items) => {
+				for item in items {
+					self.expr(item);
+				}
+			}
+			Expr::Map(entries) => {
+				for (_, value) in entries {
+					self.expr(value);
+				}
+			}
+			Expr::Index(target, index) => {
+				self.expr(target);
+				self.expr(index);
+			}
+			Expr::Get(object, ..) => self.expr(object),
+		}
+	}
+	fn stmt(&mut self, stmt: &mut Stmt) {
+		stack::grow(|| self.resolve_stmt(stmt))
+	}
+	fn resolve_stmt(&mut self, stmt: &mut Stmt) {
+		match stmt {
+			Stmt::Expression(expr) | Stmt::Print(expr) | Stmt::Throw(expr) => self.expr(expr),
+			Stmt::Var(name, value) => {
+				self.declare(name);
+				if let Some(value) = value {
+					self.expr(value);
+				}
+				self.define(name);
+			}
+			Stmt::Destructure(pattern, value) => {
+				self.declare_pattern(pattern);
+				self.expr(value);
+				self.pattern(pattern, true);
+			}
+			Stmt::Block(statements) => self.scoped(|resolver| {
+				for statement in statements {
+					resolver.stmt(statement);
+				}
+			}),
+			Stmt::If(condition, then_branch, else_branch) => {
+				self.expr(condition);
+				self.stmt(then_branch);
+				if let Some(else_branch) = else_branch {
+					self.stmt(else_branch);
+				}
+			}
+			Stmt::While(condition, body) => {
+				self.expr(condition);
+				self.loops += 1;
+				self.stmt(body);
+				self.loops -= 1;
+			}
+			Stmt::For(initializer, condition, increment, body) => self.scoped(|resolver| {
+				if let Some(initializer) = initializer {
+					resolver.stmt(initializer);
+				}
+				if let Some(condition) = condition {
+					resolver.expr(condition);
+				}
+				resolver.loops += 1;
+				resolver.stmt(body);
+				resolver.loops -= 1;
+				if let Some(increment) = increment {
+					resolver.expr(increment);
+				}
+			}),
+			Stmt::Function(name, params, body) => {
+				// Defined straight away so the function can call itself
+				let declaration = self.declare(name);
+				self.declarations[declaration].arity = Some(params.arity());
+				self.define(name);
+				self.function(params, Rc::make_mut(body));
+			}
+			Stmt::Return(value) => {
+				if self.functions == 0 {
+					self.errors
+						.push("Can't return from top-level code".to_string());
+				}
+				self.expr(value);
+			}
+			Stmt::Break | Stmt::Continue if self.loops == 0 => {
+				let keyword = if let Stmt::Break = stmt {
+					"break"
+				} else {
+					"continue"
+				};
+				self.errors
+					.push(format!("Can't `{keyword}` outside of a loop"));
+			}
+			Stmt::Break | Stmt::Continue => {}
+			Stmt::Try(body, catch, finally) => {
+				self.stmt(body);
+				if let Some((name, handler)) = catch {
+					self.scoped(|resolver| {
+						resolver.declare(name);
+						resolver.define(name);
+						resolver.stmt(handler);
+					});
+				}
+				if let Some(finally) = finally {
+					self.stmt(finally);
+				}
+			}
+		}
+	}
+}
+
+/// Bind every variable in the program to the scope it's declared in, returning the mistakes
+/// that can be caught before running it
+pub fn resolve(statements: &mut [Stmt]) -> Vec<String> {
+	let mut resolver = Resolver::default();
+	for statement in statements {
+		resolver.stmt(statement);
+	}
+
+	let arity_errors = resolver.arity_errors();
+	let mut errors = resolver.errors;
+	errors.extend(arity_errors);
+	errors
+}
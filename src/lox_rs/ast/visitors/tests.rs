@@ -0,0 +1,1221 @@
+use std::time::Instant;
+
+use anyhow::Result;
+
+use super::{
+	super::{
+		super::{
+			error::{ErrorKind, Frame, Interrupted, RuntimeError},
+			lexer::scanner::scan,
+			output::{Buffer, Output},
+			parser::Parser,
+		},
+		expr::{Literal, Shared},
+		stmt::Stmt,
+	},
+	interp::{Interperter, DEFAULT_MEMORY_LIMIT},
+	parens::parenthesize,
+	resolver::resolve,
+};
+
+fn eval(input: &[u8]) -> Result<Literal> {
+	eval_in(&mut Interperter::default(), input)
+}
+fn eval_in(interp: &mut Interperter, input: &[u8]) -> Result<Literal> {
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (mut tree, errors) = parser.parse()?;
+	assert!(errors.is_empty(), "{errors:?}");
+	let errors = resolve(&mut tree);
+	assert!(errors.is_empty(), "{errors:?}");
+
+	let mut result = Literal::Null;
+	for stmt in tree {
+		result = stmt.interpret(interp)?;
+	}
+	Ok(result)
+}
+
+/// Run a program and return what it printed
+fn printed(input: &[u8]) -> Result<String> {
+	let buffer = Buffer::new();
+	let mut interp = Interperter::default();
+	interp.output = Output::new(buffer.clone(), Buffer::new());
+	eval_in(&mut interp, input)?;
+	Ok(buffer.take())
+}
+
+#[test]
+fn sanity() -> Result<()> {
+	assert_eq!(eval(b"1 + 1;")?, Literal::Number(2.));
+	Ok(())
+}
+
+#[test]
+fn conditional_short_circuits() -> Result<()> {
+	assert_eq!(eval(b"true ? 1 : missing;")?, Literal::Number(1.));
+	assert_eq!(eval(b"false ? missing : 2;")?, Literal::Number(2.));
+	assert_eq!(eval(b"0 ? 1 : '' ? 2 : 3;")?, Literal::Number(3.));
+	Ok(())
+}
+
+#[test]
+fn coalesce_only_replaces_null() -> Result<()> {
+	assert_eq!(eval(b"null ?? 1;")?, Literal::Number(1.));
+	assert_eq!(eval(b"false ?? missing;")?, Literal::Boolean(false));
+	assert_eq!(eval(b"0 ?? missing;")?, Literal::Number(0.));
+	assert_eq!(
+		eval(b"null ?? null ?? 'x';")?,
+		Literal::String(Shared::new("x".to_string()))
+	);
+	Ok(())
+}
+
+#[test]
+fn compound_assignment() -> Result<()> {
+	assert_eq!(
+		eval(b"var i = 10; i -= 4; i *= 2; i /= 3;")?,
+		Literal::Number(4.)
+	);
+	assert_eq!(
+		eval(b"var s = 'ab'; s += 'c';")?,
+		Literal::String(Shared::new("abc".to_string()))
+	);
+	Ok(())
+}
+
+#[test]
+fn increment_and_decrement() -> Result<()> {
+	assert_eq!(eval(b"var i = 1; i++;")?, Literal::Number(1.));
+	assert_eq!(eval(b"var i = 1; ++i;")?, Literal::Number(2.));
+	assert_eq!(eval(b"var i = 1; i--; i;")?, Literal::Number(0.));
+	assert_eq!(
+		eval(b"var i = 0; var n = 0; while (i < 5) { n += i++; } n;")?,
+		Literal::Number(10.)
+	);
+	assert!(eval(b"var s = 'a'; s++;").is_err());
+	Ok(())
+}
+
+#[test]
+fn map_properties() -> Result<()> {
+	assert_eq!(
+		eval(b"var m = {a: 1}; m.a = 2; m.b = {}; m.b.c = 3; m.a += 1; m.b.c++; m;")?,
+		eval(b"var m = {a: 3, b: {c: 4}}; m;")?
+	);
+	assert_eq!(
+		eval(b"var m = {a: 1}; var copy = m; m.a = 2; copy.a;")?,
+		Literal::Number(1.)
+	);
+	assert_eq!(
+		eval(b"var x = 1; try { x.a = 2; } catch (e) { e['message']; }")?,
+		Literal::String(Shared::new(
+			"Only maps and host objects have properties, not 1".to_string()
+		))
+	);
+	Ok(())
+}
+
+#[test]
+fn updating_items() -> Result<()> {
+	assert_eq!(
+		eval(b"var xs = [1, 2]; xs[0] += 10; xs[1]++; ++xs[1]; xs;")?,
+		eval(b"[11, 4];")?
+	);
+	assert_eq!(
+		eval(b"var m = {n: 1}; [m['n']++, --m['n'], m];")?,
+		eval(b"[1, 1, {n: 1}];")?
+	);
+	// The container and index are evaluated once, even though the item is read and written
+	assert_eq!(
+		eval(b"var i = 0; var xs = [[1], [2]]; xs[i++][0] += 5; [xs, i];")?,
+		eval(b"[[[6], [2]], 1];")?
+	);
+	assert!(eval(b"var m = {}; m['missing'] += 1;").is_err());
+	Ok(())
+}
+
+#[test]
+fn modulo_follows_the_divisor_sign() -> Result<()> {
+	assert_eq!(eval(b"7 % 3;")?, Literal::Number(1.));
+	assert_eq!(eval(b"-7 % 3;")?, Literal::Number(2.));
+	assert_eq!(eval(b"7 % -3;")?, Literal::Number(-2.));
+	assert_eq!(eval(b"5.5 % 2;")?, Literal::Number(1.5));
+	assert_eq!(eval(b"var i = 10; i %= 4;")?, Literal::Number(2.));
+	// Exact even where the quotient isn't
+	assert_eq!(eval(b"100000000000000000 % 3;")?, Literal::Number(1.));
+	assert_eq!(eval(b"5 % 2 ** 1024;")?, Literal::Number(5.));
+	assert_eq!(eval(b"-5 % -(2 ** 1024);")?, Literal::Number(-5.));
+	assert!(eval(b"1 % 0;").is_err());
+	Ok(())
+}
+
+#[test]
+fn floor_division() -> Result<()> {
+	assert_eq!(eval(b"7 // 2;")?, Literal::Number(3.));
+	assert_eq!(eval(b"-7 // 2;")?, Literal::Number(-4.));
+	assert_eq!(eval(b"(-7 // 2) * 2 + -7 % 2;")?, Literal::Number(-7.));
+	assert!(eval(b"1 // 0;").is_err());
+	Ok(())
+}
+
+#[test]
+fn exponentiation() -> Result<()> {
+	assert_eq!(eval(b"2 ** 10;")?, Literal::Number(1024.));
+	assert_eq!(eval(b"2 ** 3 ** 2;")?, Literal::Number(512.));
+	assert_eq!(eval(b"-2 ** 2;")?, Literal::Number(-4.));
+	assert_eq!(eval(b"2 ** -1;")?, Literal::Number(0.5));
+	Ok(())
+}
+
+#[test]
+fn bitwise_operators() -> Result<()> {
+	assert_eq!(eval(b"12 & 10;")?, Literal::Number(8.));
+	assert_eq!(eval(b"12 | 10;")?, Literal::Number(14.));
+	assert_eq!(eval(b"12 ^ 10;")?, Literal::Number(6.));
+	assert_eq!(eval(b"~5;")?, Literal::Number(-6.));
+	assert_eq!(eval(b"1 << 4;")?, Literal::Number(16.));
+	assert_eq!(eval(b"-16 >> 2;")?, Literal::Number(-4.));
+	assert_eq!(eval(b"1 + 1 << 1;")?, Literal::Number(4.));
+	Ok(())
+}
+
+#[test]
+fn bitwise_operators_need_integers() -> Result<()> {
+	assert!(eval(b"1.5 & 1;").is_err());
+	assert!(eval(b"~0.5;").is_err());
+	assert!(eval(b"'1' | 1;").is_err());
+	assert!(eval(b"1 << 64;").is_err());
+	assert!(eval(b"1 >> -1;").is_err());
+	// 2^63 is one past `i64::MAX`
+	assert!(eval(b"9223372036854775808 | 0;").is_err());
+	assert_eq!(
+		eval(b"-9223372036854775808 | 0;")?,
+		Literal::Number(i64::MIN as f64)
+	);
+	Ok(())
+}
+
+#[test]
+fn pipeline_calls_the_right_hand_side() -> Result<()> {
+	assert_eq!(
+		eval(b"fn add(a, b) { return a + b; } 1 |> add(2);")?,
+		Literal::Number(3.)
+	);
+	Ok(())
+}
+
+#[test]
+fn parenthesize_pipeline() -> Result<()> {
+	let input = scan(b"x |> f(1) |> g;");
+	let mut parser = Parser::new(input);
+	let (tree, _) = parser.parse()?;
+
+	let [Stmt::Expression(expr)] = &tree[..] else {
+		panic!("Expected a single expression, got {tree:?}");
+	};
+	assert_eq!(
+		parenthesize(expr),
+		"(call (var g) (call (var f) (var x) 1))"
+	);
+	Ok(())
+}
+
+#[test]
+fn match_expression() -> Result<()> {
+	let describe = |n: &str| {
+		eval(
+			format!(
+				"match {n} {{
+					0 => 'zero',
+					'0' => 'string zero',
+					n if n < 0 => 'negative',
+					big if big >= 100 => 'big ' + 'number',
+					_ => 'other',
+				}};"
+			)
+			.as_bytes(),
+		)
+	};
+	assert_eq!(
+		describe("0")?,
+		Literal::String(Shared::new("zero".to_string()))
+	);
+	assert_eq!(
+		describe("'0'")?,
+		Literal::String(Shared::new("string zero".to_string()))
+	);
+	assert_eq!(
+		describe("-5")?,
+		Literal::String(Shared::new("negative".to_string()))
+	);
+	assert_eq!(
+		describe("100")?,
+		Literal::String(Shared::new("big number".to_string()))
+	);
+	assert_eq!(
+		describe("7")?,
+		Literal::String(Shared::new("other".to_string()))
+	);
+	Ok(())
+}
+
+#[test]
+fn match_bindings_are_scoped_to_their_arm() -> Result<()> {
+	assert_eq!(
+		eval(b"var n = 1; match 2 { n => n * 10 }; n;")?,
+		Literal::Number(1.)
+	);
+	Ok(())
+}
+
+#[test]
+fn match_without_a_matching_arm_fails() -> Result<()> {
+	assert!(eval(b"match 3 { 1 => 'one', n if n > 5 => 'big' };").is_err());
+	Ok(())
+}
+
+#[test]
+fn lists_and_maps() -> Result<()> {
+	assert_eq!(
+		eval(b"var xs = [1, 'two', [3]]; xs[2][0];")?,
+		Literal::Number(3.)
+	);
+	assert_eq!(
+		eval(b"var m = {a: 1, 'b c': 2}; m['b c'];")?,
+		Literal::Number(2.)
+	);
+	assert_eq!(
+		eval(b"var m = {a: 1}; m['missing'] ?? 'default';")?,
+		Literal::String(Shared::new("default".to_string()))
+	);
+	assert!(eval(b"[1, 2][2];").is_err());
+	assert!(eval(b"[1, 2][0.5];").is_err());
+	Ok(())
+}
+
+#[test]
+fn index_assignment() -> Result<()> {
+	assert_eq!(eval(b"var xs = [1, 2]; xs[0] = 5; xs;")?, eval(b"[5, 2];")?);
+	assert_eq!(
+		eval(b"var m = {a: [1]}; m['a'][0] = 2; m['b'] = 3; m;")?,
+		eval(b"var m = {a: [2], b: 3}; m;")?
+	);
+	// Lists and maps are values, assigning into one doesn't change its copies
+	assert_eq!(
+		eval(b"var a = [1]; var b = a; b[0] = 2; [a, b];")?,
+		eval(b"[[1], [2]];")?
+	);
+	// The index is evaluated once, before the value
+	assert_eq!(
+		eval(b"var i = 0; var xs = [0, 0]; xs[i++] = i; [xs, i];")?,
+		eval(b"[[1, 0], 1];")?
+	);
+	assert!(eval(b"var xs = [1]; xs[1] = 2;").is_err());
+	// Nothing is added when the assignment fails
+	assert_eq!(
+		eval(b"var m = {}; try { m['a'][0] = 1; } catch (e) {} m;")?,
+		eval(b"var m = {}; m;")?
+	);
+	Ok(())
+}
+
+#[test]
+fn destructuring_lists() -> Result<()> {
+	assert_eq!(
+		eval(b"var [a, b, ...rest] = [1, 2, 3, 4]; [a, b, rest];")?,
+		eval(b"[1, 2, [3, 4]];")?
+	);
+	assert_eq!(
+		eval(b"var [a, b = a * 10] = [4]; b;")?,
+		Literal::Number(40.)
+	);
+	assert_eq!(eval(b"var [_, [x]] = [1, [2]]; x;")?, Literal::Number(2.));
+	assert!(eval(b"var [a, b] = [1];").is_err());
+	assert!(eval(b"var [a] = [1, 2];").is_err());
+	assert!(eval(b"var [a] = 'a';").is_err());
+	Ok(())
+}
+
+#[test]
+fn destructuring_maps() -> Result<()> {
+	assert_eq!(
+		eval(b"var {x, y: py, z = 3} = {x: 1, y: 2, w: 0}; [x, py, z];")?,
+		eval(b"[1, 2, 3];")?
+	);
+	assert!(eval(b"var {x} = {y: 1};").is_err());
+	Ok(())
+}
+
+#[test]
+fn destructuring_parameters() -> Result<()> {
+	assert_eq!(
+		eval(b"fn dist({x, y}, [dx, dy]) { return (x - dx) ** 2 + (y - dy) ** 2; } dist({x: 4, y: 5}, [1, 1]);")?,
+		Literal::Number(25.)
+	);
+	Ok(())
+}
+
+#[test]
+fn match_collection_patterns() -> Result<()> {
+	let shape = |s: &str| {
+		eval(
+			format!(
+				"match {s} {{
+					{{kind: 'circle', r}} => 3 * r ** 2,
+					{{kind: 'rect', size: [w, h]}} => w * h,
+					[] => 0,
+					[first, ...rest] => first,
+				}};"
+			)
+			.as_bytes(),
+		)
+	};
+	assert_eq!(shape("{kind: 'circle', r: 2}")?, Literal::Number(12.));
+	assert_eq!(shape("{kind: 'rect', size: [2, 3]}")?, Literal::Number(6.));
+	assert_eq!(shape("[]")?, Literal::Number(0.));
+	assert_eq!(shape("[5, 6]")?, Literal::Number(5.));
+	assert!(shape("{kind: 'rect'}").is_err());
+	Ok(())
+}
+
+#[test]
+fn default_parameters() -> Result<()> {
+	let call = |args: &str| {
+		eval(
+			format!("fn f(a, b = a * 2, c = [a, b]) {{ return [a, b, c]; }} f({args});").as_bytes(),
+		)
+	};
+	assert_eq!(call("1")?, eval(b"[1, 2, [1, 2]];")?);
+	assert_eq!(call("1, 5")?, eval(b"[1, 5, [1, 5]];")?);
+	assert_eq!(call("1, 5, null")?, eval(b"[1, 5, null];")?);
+	Ok(())
+}
+
+#[test]
+fn rest_parameters() -> Result<()> {
+	let call = |args: &str| {
+		eval(format!("fn f(first, ...rest) {{ return [first, rest]; }} f({args});").as_bytes())
+	};
+	assert_eq!(call("1")?, eval(b"[1, []];")?);
+	assert_eq!(call("1, 2, 3")?, eval(b"[1, [2, 3]];")?);
+	Ok(())
+}
+
+#[test]
+fn strict_arity() -> Result<()> {
+	// Called through a variable so it's checked while running rather than by the resolver
+	let err = eval(b"fn f(a, b = 1) { return a; } var g = f; g();").unwrap_err();
+	assert_eq!(err.to_string(), "`f` expected 1 to 2 arguments but got 0");
+
+	let err = eval(b"fn f(a) { return a; } var g = f; g(1, 2);").unwrap_err();
+	assert_eq!(err.to_string(), "`f` expected 1 arguments but got 2");
+
+	let err = eval(b"fn f(a, ...rest) { return a; } var g = f; g();").unwrap_err();
+	assert_eq!(
+		err.to_string(),
+		"`f` expected at least 1 arguments but got 0"
+	);
+	Ok(())
+}
+
+#[test]
+fn native_arity_is_checked() -> Result<()> {
+	let err = eval(b"now(1);").unwrap_err();
+	assert_eq!(err.to_string(), "`now` expected 0 arguments but got 1");
+	Ok(())
+}
+
+#[test]
+fn argument_errors_propagate() -> Result<()> {
+	let err = eval(b"now(missing);").unwrap_err();
+	assert_eq!(err.to_string(), "Undefined variable `missing`");
+	Ok(())
+}
+
+#[test]
+fn arguments_are_evaluated_once() -> Result<()> {
+	assert_eq!(
+		eval(b"fn f(a) { a; } var i = 0; f(i++); i;")?,
+		Literal::Number(1.)
+	);
+	Ok(())
+}
+
+#[test]
+fn static_arity_check() -> Result<()> {
+	let check = |input: &[u8]| -> Result<Vec<String>> {
+		let mut parser = Parser::new(scan(input));
+		let (mut tree, errors) = parser.parse()?;
+		assert!(errors.is_empty(), "{errors:?}");
+		Ok(resolve(&mut tree))
+	};
+
+	assert_eq!(
+		check(b"fn f(a, b = 1) {} f(1); f(1, 2, 3);")?,
+		&["`f` expected 1 to 2 arguments but got 3"]
+	);
+	assert_eq!(
+		check(b"fn f(a) { return f(); }")?,
+		&["`f` expected 1 arguments but got 0"]
+	);
+	assert!(check(b"fn f(...rest) {} f(); f(1, 2, 3);")?.is_empty());
+	assert!(check(b"fn f(a) {} { var f = now; f(); }")?.is_empty());
+	assert!(check(b"fn f(a) {} fn g() {} f = g; f();")?.is_empty());
+	assert!(check(b"g(1); fn g() {}")?.is_empty());
+	// Functions can call globals declared after them
+	assert_eq!(
+		check(b"fn f() { return g(1); } fn g() {}")?,
+		&["`g` expected 0 arguments but got 1"]
+	);
+	// Which are replaced when they're declared again
+	assert!(check(b"fn f(a) {} f(); fn f() {}")?.is_empty());
+	assert!(check(b"fn f(a) {} var f = now; f();")?.is_empty());
+	Ok(())
+}
+
+#[test]
+fn closures_share_their_environment() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"var counter;
+			{
+				var i = 0;
+				fn inc() { i += 1; }
+				counter = inc;
+			}
+			counter();
+			counter();
+			counter();"
+		)?,
+		Literal::Number(3.)
+	);
+	assert_eq!(
+		eval(
+			b"var get; var set;
+			{
+				var shared = 'before';
+				fn g() { shared; }
+				fn s(value) { shared = value; }
+				get = g;
+				set = s;
+			}
+			set('after');
+			get();"
+		)?,
+		Literal::String(Shared::new("after".to_string()))
+	);
+	Ok(())
+}
+
+#[test]
+fn closures_see_later_updates() -> Result<()> {
+	assert_eq!(
+		eval(b"var x = 1; fn get() { x; } x = 2; get();")?,
+		Literal::Number(2.)
+	);
+	Ok(())
+}
+
+#[test]
+fn each_call_gets_its_own_scope() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"fn make(start) {
+				fn inc() { start += 1; }
+				inc;
+			}
+			var a = make(0);
+			var b = make(10);
+			a(); a(); b();
+			[a(), b()];"
+		)?,
+		eval(b"[3, 12];")?
+	);
+	Ok(())
+}
+
+#[test]
+fn closures_bind_to_the_declaration_in_scope() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"var a = 'global';
+			{
+				fn show() { a; }
+				var first = show();
+				var a = 'block';
+				[first, show(), a];
+			}"
+		)?,
+		eval(b"['global', 'global', 'block'];")?
+	);
+	Ok(())
+}
+
+#[test]
+fn resolver_errors() -> Result<()> {
+	let check = |input: &[u8]| -> Result<Vec<String>> {
+		let mut parser = Parser::new(scan(input));
+		let (mut tree, errors) = parser.parse()?;
+		assert!(errors.is_empty(), "{errors:?}");
+		Ok(resolve(&mut tree))
+	};
+
+	assert_eq!(
+		check(b"var a = 1; { var a = a; }")?,
+		&["Can't read local variable `a` in its own initializer"]
+	);
+	assert_eq!(
+		check(b"{ var [a, b] = [b, 1]; }")?,
+		&["Can't read local variable `b` in its own initializer"]
+	);
+	assert_eq!(
+		check(b"fn f(a) { var b; var b; } { var [c, c] = [1, 2]; }")?,
+		&[
+			"Already a variable named `b` in this scope",
+			"Already a variable named `c` in this scope"
+		]
+	);
+	assert_eq!(check(b"return 1;")?, &["Can't return from top-level code"]);
+	// Globals can be redeclared, and read from their own initializer
+	assert!(check(b"var a = 1; var a = a; fn f() { return a; }")?.is_empty());
+	assert!(check(b"{ var [a, b = a] = [1]; }")?.is_empty());
+	Ok(())
+}
+
+#[test]
+fn locals_are_found_across_scopes() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"fn outer() {
+				var a = 1;
+				var b = 2;
+				fn inner(c) {
+					var d = 4;
+					{
+						var a = 5;
+						a + b * 10 + c * 100 + d * 1000;
+					}
+				}
+				inner(3) + a;
+			}
+			outer();"
+		)?,
+		Literal::Number(4326.)
+	);
+	assert_eq!(
+		eval(
+			b"fn sum(limit) {
+				var i = 0;
+				var total = 0;
+				while (i < limit) {
+					var next = i + 1;
+					total += next;
+					i = next;
+				}
+				total;
+			}
+			sum(4);"
+		)?,
+		Literal::Number(10.)
+	);
+	Ok(())
+}
+
+#[test]
+fn return_exits_early() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"fn find(xs, target) {
+				var i = 0;
+				while (true) {
+					if (xs[i] == target) {
+						return i;
+					}
+					i += 1;
+				}
+			}
+			find([4, 5, 6], 5);"
+		)?,
+		Literal::Number(1.)
+	);
+	assert_eq!(
+		eval(
+			b"fn sign(n) {
+				if (n < 0) return -1; else if (n > 0) return 1;
+				return 0;
+			}
+			[sign(-3), sign(0), sign(7)];"
+		)?,
+		eval(b"[-1, 0, 1];")?
+	);
+	Ok(())
+}
+
+#[test]
+fn return_does_not_leak() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"fn add(a, b) { return a + b; }
+			fn double(n) { return n * 2; }
+			var first = double(add(1, 2));
+			var second = add(10, 20);
+			[first, second];"
+		)?,
+		eval(b"[6, 30];")?
+	);
+	Ok(())
+}
+
+#[test]
+fn break_and_continue() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"var odds = 0;
+			for (var i = 0; i < 10; i++) {
+				if (i == 7) break;
+				if (i % 2 == 0) continue;
+				odds += i;
+			}
+			odds;"
+		)?,
+		Literal::Number(9.)
+	);
+	assert_eq!(
+		eval(
+			b"var i = 0;
+			while (true) {
+				i++;
+				{
+					if (i < 3) continue;
+				}
+				break;
+			}
+			i;"
+		)?,
+		Literal::Number(3.)
+	);
+	Ok(())
+}
+
+#[test]
+fn loop_control_outside_a_loop() -> Result<()> {
+	let mut parser = Parser::new(scan(b"break; while (true) { fn f() { continue; } }"));
+	let (mut tree, errors) = parser.parse()?;
+	assert!(errors.is_empty(), "{errors:?}");
+
+	assert_eq!(
+		resolve(&mut tree),
+		&[
+			"Can't `break` outside of a loop",
+			"Can't `continue` outside of a loop"
+		]
+	);
+	Ok(())
+}
+
+#[test]
+fn top_level_functions_are_mutually_recursive() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"fn is_even(n) {
+				if (n == 0) return true;
+				return is_odd(n - 1);
+			}
+			fn is_odd(n) {
+				if (n == 0) return false;
+				return is_even(n - 1);
+			}
+			[is_even(10), is_odd(7), is_even(3)];"
+		)?,
+		eval(b"[true, true, false];")?
+	);
+	Ok(())
+}
+
+#[test]
+fn globals_are_late_bound() -> Result<()> {
+	// Functions can use globals declared after them, as long as they exist by the time of the call
+	assert_eq!(
+		eval(
+			b"fn twice() { return helper() * 2; }
+			fn helper() { return limit; }
+			var limit = 21;
+			twice();"
+		)?,
+		Literal::Number(42.)
+	);
+	// And see whatever the global holds when they're called, even after it's redeclared
+	assert_eq!(
+		eval(
+			b"fn get() { return x; }
+			var x = 1;
+			var first = get();
+			var x = 2;
+			[first, get()];"
+		)?,
+		eval(b"[1, 2];")?
+	);
+	assert_eq!(
+		eval(b"fn f() { return missing; } f();")
+			.unwrap_err()
+			.to_string(),
+		"Undefined variable `missing`\n    in `f`, called at line 1, column 29"
+	);
+	Ok(())
+}
+
+#[test]
+fn redeclared_functions_are_late_bound() -> Result<()> {
+	// Calls go to the latest declaration, even from code that was checked before it
+	assert_eq!(
+		eval(
+			b"fn f(a) { return 1; }
+			fn g() { return f(); }
+			fn f() { return 2; }
+			g();"
+		)?,
+		Literal::Number(2.)
+	);
+	Ok(())
+}
+
+#[test]
+fn local_functions_are_not_late_bound() -> Result<()> {
+	// Unlike globals, a local is only visible to the code after its declaration
+	assert_eq!(
+		eval(
+			b"fn outer() {
+				fn first() { return second(); }
+				fn second() { return 'local'; }
+				return first();
+			}
+			fn second() { return 'global'; }
+			outer();"
+		)?,
+		Literal::String(Shared::new("global".to_string()))
+	);
+	Ok(())
+}
+
+#[test]
+fn runtime_errors_carry_the_call_stack() -> Result<()> {
+	let err = eval(
+		b"fn inner(x) {\n\treturn x + missing;\n}\nfn outer() {\n\treturn inner(1);\n}\nouter();",
+	)
+	.unwrap_err();
+	assert_eq!(
+		err.to_string(),
+		"Undefined variable `missing`
+    in `inner`, called at line 5, column 14
+    in `outer`, called at line 7, column 6"
+	);
+
+	let Some(RuntimeError { trace, .. }) = err.downcast_ref::<RuntimeError>() else {
+		panic!("Expected a runtime error, got {err:?}");
+	};
+	assert_eq!(
+		trace
+			.iter()
+			.map(|Frame { function, .. }| function)
+			.collect::<Vec<_>>(),
+		&["inner", "outer"]
+	);
+
+	// Errors in the arguments happen before the call, in the caller
+	let err = eval(b"fn f(x) { return x; } f(missing);").unwrap_err();
+	assert_eq!(err.to_string(), "Undefined variable `missing`");
+	Ok(())
+}
+
+#[test]
+fn exit_stops_the_program() -> Result<()> {
+	let exit_code = |input: &[u8]| {
+		let err = eval(input).unwrap_err();
+		err.downcast_ref::<RuntimeError>()
+			.and_then(RuntimeError::exit_code)
+	};
+
+	assert_eq!(exit_code(b"exit(3); 1;"), Some(3));
+	assert_eq!(exit_code(b"fn f() { while (true) exit(0); } f();"), Some(0));
+	assert_eq!(exit_code(b"exit(256);"), None);
+	assert_eq!(exit_code(b"exit('1');"), None);
+	Ok(())
+}
+
+#[test]
+fn thrown_values_are_caught_as_they_are() -> Result<()> {
+	assert_eq!(
+		eval(b"try { throw {code: 1}; } catch (e) { e; }")?,
+		eval(b"var e = {code: 1}; e;")?
+	);
+	assert_eq!(
+		eval(b"fn f() { throw 'deep'; } try { f(); 'unreachable'; } catch (e) { e; }")?,
+		Literal::String(Shared::new("deep".to_string()))
+	);
+	assert_eq!(
+		eval(b"throw 'boom';").unwrap_err().to_string(),
+		"Uncaught \"boom\""
+	);
+	Ok(())
+}
+
+#[test]
+fn runtime_errors_are_caught_as_maps() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"fn f() { return missing; }
+			try { f(); } catch (e) { [e['kind'], e['message'], e['trace'][0]['function']]; }"
+		)?,
+		eval(b"['NameError', 'Undefined variable `missing`', 'f'];")?
+	);
+	assert_eq!(
+		eval(b"try { 1 + 'a'; } catch (e) { e['kind']; }")?,
+		Literal::String(Shared::new("TypeError".to_string()))
+	);
+	assert_eq!(
+		eval(b"try { [1][3]; } catch (e) { e['kind']; }")?,
+		Literal::String(Shared::new("IndexError".to_string()))
+	);
+	assert_eq!(
+		eval(b"fn f(a) {} var g = f; try { g(); } catch (e) { e['kind']; }")?,
+		Literal::String(Shared::new("ArityError".to_string()))
+	);
+	Ok(())
+}
+
+#[test]
+fn finally_always_runs() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"var log = [];
+			fn attempt(fail) {
+				try {
+					if (fail) throw 'failed';
+					return 'returned';
+				} catch (e) {
+					log = [log, e];
+				} finally {
+					log = [log, 'finally'];
+				}
+			}
+			var returned = attempt(false);
+			attempt(true);
+			[returned, log];"
+		)?,
+		eval(b"['returned', [[[[], 'finally'], 'failed'], 'finally']];")?
+	);
+	// It runs even when nothing catches the error, which then keeps unwinding
+	assert_eq!(
+		eval(
+			b"var cleaned = false;
+			try {
+				try { throw 'inner'; } finally { cleaned = true; }
+			} catch (e) {
+				[e, cleaned];
+			}"
+		)?,
+		eval(b"['inner', true];")?
+	);
+	// And if it unwinds itself, that wins
+	assert_eq!(
+		eval(b"fn f() { try { return 1; } finally { return 2; } } f();")?,
+		Literal::Number(2.)
+	);
+	Ok(())
+}
+
+#[test]
+fn errors_in_finally_blocks_do_not_lose_the_pending_one() -> Result<()> {
+	assert_eq!(
+		eval(
+			b"try {
+				try { throw 'first'; } finally {
+					try { throw 'second'; } catch (e) {}
+				}
+			} catch (e) {
+				e;
+			}"
+		)?,
+		Literal::String(Shared::new("first".to_string()))
+	);
+	Ok(())
+}
+
+#[test]
+fn exit_is_not_caught() -> Result<()> {
+	let err = eval(b"try { exit(2); } catch (e) { 'caught'; }").unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<RuntimeError>()
+			.and_then(RuntimeError::exit_code),
+		Some(2)
+	);
+	Ok(())
+}
+
+#[test]
+fn deep_recursion_is_a_stack_overflow_error() -> Result<()> {
+	// Far deeper than the test thread's stack would fit on its own
+	assert_eq!(
+		eval(b"fn count(n) { return n == 0 ? 0 : 1 + count(n - 1); } count(999);")?,
+		Literal::Number(999.)
+	);
+
+	let err = eval(b"fn forever() { return forever(); } forever();").unwrap_err();
+	let err = err.downcast::<RuntimeError>()?;
+	assert_eq!(
+		err.error.to_string(),
+		"Stack overflow: more than 1000 nested calls"
+	);
+	// Including the call that didn't fit
+	assert_eq!(err.trace.len(), 1001);
+	assert_eq!(
+		eval(b"fn forever() { return forever(); } try { forever(); } catch (e) { e['kind']; }")?,
+		Literal::String(Shared::new("StackOverflowError".to_string()))
+	);
+	Ok(())
+}
+
+#[test]
+fn max_call_depth_is_configurable() -> Result<()> {
+	let mut interp = Interperter::default();
+	interp.max_call_depth = 10;
+
+	let count = b"fn count(n) { return n == 0 ? 0 : 1 + count(n - 1); }";
+	eval_in(&mut interp, count)?;
+	assert_eq!(eval_in(&mut interp, b"count(9);")?, Literal::Number(9.));
+	let err = eval_in(&mut interp, b"count(10);").unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<RuntimeError>()
+			.map(|err| err.error.to_string()),
+		Some("Stack overflow: more than 10 nested calls".to_string())
+	);
+	// The calls that failed don't count against the next ones
+	assert_eq!(eval_in(&mut interp, b"count(9);")?, Literal::Number(9.));
+	Ok(())
+}
+
+#[test]
+fn fuel_is_used_up_per_statement_and_expression() -> Result<()> {
+	let mut interp = Interperter::default();
+	interp.fuel = Some(10);
+
+	// The statement, the addition and both its operands
+	eval_in(&mut interp, b"1 + 1;")?;
+	assert_eq!(interp.fuel, Some(6));
+
+	let err = eval_in(&mut interp, b"while (true) {}").unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<RuntimeError>()
+			.and_then(RuntimeError::interrupted),
+		Some(Interrupted::OutOfFuel)
+	);
+	Ok(())
+}
+
+#[test]
+fn running_out_of_budget_is_not_caught() -> Result<()> {
+	let mut interp = Interperter::default();
+	interp.fuel = Some(1000);
+	let err = eval_in(
+		&mut interp,
+		b"while (true) { try { while (true) {} } catch (e) {} finally {} }",
+	)
+	.unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<RuntimeError>()
+			.and_then(RuntimeError::interrupted),
+		Some(Interrupted::OutOfFuel)
+	);
+
+	let mut interp = Interperter::default();
+	interp.deadline = Some(Instant::now());
+	let err = eval_in(
+		&mut interp,
+		b"fn spin() { while (true) {} } try { spin(); } catch (e) {}",
+	)
+	.unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<RuntimeError>()
+			.and_then(RuntimeError::interrupted),
+		Some(Interrupted::DeadlineExceeded)
+	);
+	Ok(())
+}
+
+#[test]
+fn values_are_limited_to_the_memory_limit() -> Result<()> {
+	let mut interp = Interperter::default();
+	interp.memory_limit = Some(1 << 16);
+	// Lists and maps share what's in them, so each one only counts once, not once per copy
+	assert_eq!(
+		eval_in(
+			&mut interp,
+			b"var l = [1]; for (var i = 0; i < 100; i = i + 1) l = [l, l]; true;"
+		)?,
+		Literal::Boolean(true)
+	);
+
+	let mut interp = Interperter::default();
+	interp.memory_limit = Some(1024);
+	assert_eq!(
+		eval_in(&mut interp, b"'ab' * 512;")?,
+		Literal::String(Shared::new("ab".repeat(512)))
+	);
+	let err = eval_in(&mut interp, b"'x' * 1000000000000;").unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<RuntimeError>()
+			.map(|err| err.error.to_string()),
+		Some(
+			"Out of memory: 1000000000000 more bytes would take the 0 in use over the limit of 1024"
+				.to_string()
+		)
+	);
+	// It's a Lox error like any other
+	assert_eq!(
+		eval_in(
+			&mut interp,
+			b"try { var s = 'ab'; while (true) s += s; } catch (e) { e['kind']; }"
+		)?,
+		Literal::String(Shared::new("OutOfMemoryError".to_string()))
+	);
+	let items = vec!["1"; 1024].join(", ");
+	let err = eval_in(&mut interp, format!("[{items}];").as_bytes()).unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<RuntimeError>()
+			.map(|err| ErrorKind::of(&err.error)),
+		Some(ErrorKind::OutOfMemory)
+	);
+	Ok(())
+}
+
+#[test]
+fn the_memory_limit_is_for_all_values_together() -> Result<()> {
+	let mut interp = Interperter::default();
+	interp.memory_limit = Some(1000);
+
+	// Each string fits on its own, but not both at once
+	eval_in(&mut interp, b"var a = 'x' * 600;")?;
+	let err = eval_in(&mut interp, b"var b = 'y' * 600;").unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<RuntimeError>()
+			.map(|err| ErrorKind::of(&err.error)),
+		Some(ErrorKind::OutOfMemory)
+	);
+	// What's dropped or overwritten is given back
+	eval_in(&mut interp, b"a = null; var b = 'y' * 600; b = 'z' * 300;")?;
+	assert_eq!(interp.memory_in_use(), 300);
+	eval_in(&mut interp, b"b = null;")?;
+	assert_eq!(interp.memory_in_use(), 0);
+
+	assert_eq!(
+		eval_in(
+			&mut interp,
+			b"var l = [];
+			try { while (true) l = [l, 'x' * 90]; } catch (e) { e['kind']; }"
+		)?,
+		Literal::String(Shared::new("OutOfMemoryError".to_string()))
+	);
+	// So do the entries added to a map
+	eval_in(&mut interp, b"l = null; var m = {};")?;
+	assert_eq!(
+		eval_in(
+			&mut interp,
+			b"var k = ''; try { while (true) { k += 'k'; m[k] = k; } } catch (e) { e['kind']; }"
+		)?,
+		Literal::String(Shared::new("OutOfMemoryError".to_string()))
+	);
+	Ok(())
+}
+
+#[test]
+fn memory_is_limited_by_default() -> Result<()> {
+	assert_eq!(
+		Interperter::default().memory_limit,
+		Some(DEFAULT_MEMORY_LIMIT)
+	);
+	assert_eq!(
+		eval(b"try { 'x' * 1000000000000; } catch (e) { e['kind']; }")?,
+		Literal::String(Shared::new("OutOfMemoryError".to_string()))
+	);
+	// Without a limit, an allocation that fails is still an error rather than an abort
+	let mut interp = Interperter::default();
+	interp.memory_limit = None;
+	let err = eval_in(&mut interp, b"'x' * 1000000000000000000;").unwrap_err();
+	assert_eq!(
+		err.downcast_ref::<RuntimeError>()
+			.map(|err| err.error.to_string()),
+		Some("Out of memory: couldn't allocate 1000000000000000000 bytes".to_string())
+	);
+	Ok(())
+}
+
+#[test]
+fn values_can_be_nested_deeply() -> Result<()> {
+	// Dropping, comparing and printing them all recurse once per level
+	let output = printed(
+		b"var l = []; var m = {};
+		for (var i = 0; i < 30000; i = i + 1) { l = [l]; m = {m: m}; }
+		print l == [l[0]]; print m == m; print l;",
+	)?;
+	assert_eq!(
+		output,
+		format!("true\ntrue\n{}{}\n", "[".repeat(30001), "]".repeat(30001))
+	);
+	Ok(())
+}
+
+#[test]
+fn programs_can_be_nested_deeply() -> Result<()> {
+	// Parsing, resolving, running and dropping the tree all recurse once per level, far deeper
+	// than a test thread's stack goes
+	let depth = 30_000;
+	let program = format!("fn f() {{ return {}1; }} f();", "- ".repeat(depth));
+	let mut parser = Parser::new(scan(program.as_bytes())).max_depth(depth + 10);
+	let (mut tree, errors) = parser.parse()?;
+	assert!(errors.is_empty(), "{errors:?}");
+	let errors = resolve(&mut tree);
+	assert!(errors.is_empty(), "{errors:?}");
+
+	let mut interp = Interperter::default();
+	let mut value = Literal::Null;
+	for stmt in &tree {
+		value = stmt.interpret(&mut interp)?;
+	}
+	assert_eq!(value, Literal::Number(1.));
+	drop(tree);
+	Ok(())
+}
+
+#[test]
+fn print_writes_each_value_on_a_line() -> Result<()> {
+	assert_eq!(
+		printed(b"print 1.5; print 'a'; print [1, null]; print {'k': true};")?,
+		"1.5\n\"a\"\n[1, null]\n{\"k\": true}\n"
+	);
+	Ok(())
+}
+
+#[test]
+fn print_follows_control_flow() -> Result<()> {
+	let program = b"
+		fn f(n) {
+			try {
+				print n;
+				if (n > 1) throw n;
+				return n;
+			} finally {
+				print 'finally';
+			}
+		}
+		for (var i = 1; i <= 2; i += 1) {
+			try { print f(i); } catch (e) { print 'caught'; }
+		}
+	";
+	assert_eq!(
+		printed(program)?,
+		"1\n\"finally\"\n1\n2\n\"finally\"\n\"caught\"\n"
+	);
+	Ok(())
+}
@@ -0,0 +1,244 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	hash::BuildHasher,
+};
+
+use anyhow::{bail, Result};
+
+use super::{
+	ast::expr::{Literal, Shared},
+	error::ErrorKind,
+	host::Host,
+};
+
+/// Rust values that can be handed to Lox
+pub trait IntoLox {
+	fn into_lox(self) -> Literal;
+}
+/// Rust values that can be taken out of Lox, failing with a `TypeError` if the value doesn't fit
+pub trait FromLox: Sized {
+	fn from_lox(value: Literal) -> Result<Self>;
+}
+
+fn expected<T>(what: &str, value: &Literal) -> Result<T> {
+	bail!(ErrorKind::Type.error(format!("Expected {what}, not {value}")))
+}
+
+impl IntoLox for Literal {
+	fn into_lox(self) -> Literal {
+		self
+	}
+}
+impl FromLox for Literal {
+	fn from_lox(value: Literal) -> Result<Self> {
+		Ok(value)
+	}
+}
+
+impl IntoLox for () {
+	fn into_lox(self) -> Literal {
+		Literal::Null
+	}
+}
+
+impl IntoLox for f64 {
+	fn into_lox(self) -> Literal {
+		Literal::Number(self)
+	}
+}
+impl FromLox for f64 {
+	fn from_lox(value: Literal) -> Result<Self> {
+		match value {
+			Literal::Number(n) => Ok(n),
+			other => expected("a number", &other),
+		}
+	}
+}
+impl IntoLox for f32 {
+	fn into_lox(self) -> Literal {
+		Literal::Number(self.into())
+	}
+}
+impl FromLox for f32 {
+	fn from_lox(value: Literal) -> Result<Self> {
+		f64::from_lox(value).map(|n| n as f32)
+	}
+}
+
+/// Lox only has `f64`s, so integers have to be whole numbers in the type's range. The biggest
+/// `i64`s and `u64`s round up to a power of two as an `f64`, one past the range, so adding one
+/// to that is still the same power and the upper bound has to be strict.
+macro_rules! integer {
+	($($int:ty),*) => {$(
+		impl IntoLox for $int {
+			fn into_lox(self) -> Literal {
+				Literal::Number(self as f64)
+			}
+		}
+		impl FromLox for $int {
+			fn from_lox(value: Literal) -> Result<Self> {
+				match value {
+					Literal::Number(n)
+						if n.fract() == 0. && n >= <$int>::MIN as f64 && n < <$int>::MAX as f64 + 1. =>
+					{
+						Ok(n as $int)
+					}
+					other => expected(
+						&format!("a whole number from {} to {}", <$int>::MIN, <$int>::MAX),
+						&other,
+					),
+				}
+			}
+		}
+	)*};
+}
+integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl IntoLox for bool {
+	fn into_lox(self) -> Literal {
+		Literal::Boolean(self)
+	}
+}
+impl FromLox for bool {
+	fn from_lox(value: Literal) -> Result<Self> {
+		match value {
+			Literal::Boolean(b) => Ok(b),
+			other => expected("a boolean", &other),
+		}
+	}
+}
+
+impl IntoLox for String {
+	fn into_lox(self) -> Literal {
+		Literal::String(Shared::new(self))
+	}
+}
+impl IntoLox for &str {
+	fn into_lox(self) -> Literal {
+		Literal::String(Shared::new(self.to_string()))
+	}
+}
+impl FromLox for String {
+	fn from_lox(value: Literal) -> Result<Self> {
+		match value {
+			Literal::String(s) => Ok(Shared::unwrap_or_clone(s)),
+			other => expected("a string", &other),
+		}
+	}
+}
+
+impl IntoLox for Host {
+	fn into_lox(self) -> Literal {
+		Literal::HostObject(self)
+	}
+}
+impl FromLox for Host {
+	fn from_lox(value: Literal) -> Result<Self> {
+		match value {
+			Literal::HostObject(host) => Ok(host),
+			other => expected("a host object", &other),
+		}
+	}
+}
+
+/// `None` is `null`
+impl<T: IntoLox> IntoLox for Option<T> {
+	fn into_lox(self) -> Literal {
+		self.map_or(Literal::Null, T::into_lox)
+	}
+}
+impl<T: FromLox> FromLox for Option<T> {
+	fn from_lox(value: Literal) -> Result<Self> {
+		match value {
+			Literal::Null => Ok(None),
+			other => T::from_lox(other).map(Some),
+		}
+	}
+}
+
+impl<T: IntoLox> IntoLox for Vec<T> {
+	fn into_lox(self) -> Literal {
+		Literal::List(Shared::new(self.into_iter().map(T::into_lox).collect()))
+	}
+}
+impl<T: FromLox> FromLox for Vec<T> {
+	fn from_lox(value: Literal) -> Result<Self> {
+		match value {
+			Literal::List(items) => Shared::unwrap_or_clone(items)
+				.into_iter()
+				.map(T::from_lox)
+				.collect(),
+			other => expected("a list", &other),
+		}
+	}
+}
+
+impl<T: IntoLox, S> IntoLox for HashMap<String, T, S> {
+	fn into_lox(self) -> Literal {
+		Literal::Map(Shared::new(
+			self.into_iter().map(|(k, v)| (k, v.into_lox())).collect(),
+		))
+	}
+}
+impl<T: FromLox, S: BuildHasher + Default> FromLox for HashMap<String, T, S> {
+	fn from_lox(value: Literal) -> Result<Self> {
+		match value {
+			Literal::Map(entries) => Shared::unwrap_or_clone(entries)
+				.into_iter()
+				.map(|(k, v)| Ok((k, T::from_lox(v)?)))
+				.collect(),
+			other => expected("a map", &other),
+		}
+	}
+}
+impl<T: IntoLox> IntoLox for BTreeMap<String, T> {
+	fn into_lox(self) -> Literal {
+		Literal::Map(Shared::new(
+			self.into_iter().map(|(k, v)| (k, v.into_lox())).collect(),
+		))
+	}
+}
+impl<T: FromLox> FromLox for BTreeMap<String, T> {
+	fn from_lox(value: Literal) -> Result<Self> {
+		match value {
+			Literal::Map(entries) => Shared::unwrap_or_clone(entries)
+				.into_iter()
+				.map(|(k, v)| Ok((k, T::from_lox(v)?)))
+				.collect(),
+			other => expected("a map", &other),
+		}
+	}
+}
+
+/// Tuples are lists of exactly as many items
+macro_rules! tuple {
+	($len:literal; $($item:ident),+) => {
+		impl<$($item: IntoLox),+> IntoLox for ($($item,)+) {
+			#[allow(non_snake_case)]
+			fn into_lox(self) -> Literal {
+				let ($($item,)+) = self;
+				Literal::List(Shared::new(vec![$($item.into_lox()),+]))
+			}
+		}
+		impl<$($item: FromLox),+> FromLox for ($($item,)+) {
+			fn from_lox(value: Literal) -> Result<Self> {
+				match value {
+					Literal::List(items) if items.len() == $len => {
+						let mut items = Shared::unwrap_or_clone(items).into_iter();
+						Ok(($($item::from_lox(items.next().unwrap_or_default())?,)+))
+					}
+					other => expected(concat!("a list of ", $len, " items"), &other),
+				}
+			}
+		}
+	};
+}
+tuple!(1; A);
+tuple!(2; A, B);
+tuple!(3; A, B, C);
+tuple!(4; A, B, C, D);
+tuple!(5; A, B, C, D, E);
+tuple!(6; A, B, C, D, E, F);
+
+#[cfg(test)]
+mod tests;
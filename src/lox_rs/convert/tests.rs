@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use super::{
+	super::{
+		ast::expr::{Literal, Shared},
+		error::ErrorKind,
+	},
+	FromLox,
+	IntoLox,
+};
+
+fn round_trip<T: IntoLox + FromLox + Clone + PartialEq + std::fmt::Debug>(value: T) -> Result<()> {
+	assert_eq!(T::from_lox(value.clone().into_lox())?, value);
+	Ok(())
+}
+
+#[test]
+fn values_survive_a_round_trip() -> Result<()> {
+	round_trip(1.5)?;
+	round_trip(-3i32)?;
+	round_trip(200u8)?;
+	round_trip(true)?;
+	round_trip("lox".to_string())?;
+	round_trip(Some(1.))?;
+	round_trip(None::<f64>)?;
+	round_trip(vec![1u32, 2, 3])?;
+	round_trip(HashMap::from([("a".to_string(), vec![true])]))?;
+	round_trip((1., "two".to_string(), (false,)))?;
+	Ok(())
+}
+
+#[test]
+fn values_look_like_lox_values() {
+	assert_eq!(
+		"a".into_lox(),
+		Literal::String(Shared::new("a".to_string()))
+	);
+	assert_eq!(None::<String>.into_lox(), Literal::Null);
+	assert_eq!(().into_lox(), Literal::Null);
+	assert_eq!(
+		(1, vec!["x"]).into_lox(),
+		Literal::List(Shared::new(vec![
+			Literal::Number(1.),
+			Literal::List(Shared::new(vec![Literal::String(Shared::new(
+				"x".to_string()
+			))]))
+		]))
+	);
+}
+
+#[test]
+fn values_that_do_not_fit_are_type_errors() {
+	let cases = [
+		(
+			String::from_lox(Literal::Number(1.)).err(),
+			"Expected a string, not 1",
+		),
+		(
+			u8::from_lox(Literal::Number(256.)).err(),
+			"Expected a whole number from 0 to 255, not 256",
+		),
+		(
+			i64::from_lox(Literal::Number(0.5)).err(),
+			"Expected a whole number from -9223372036854775808 to 9223372036854775807, not 0.5",
+		),
+		(
+			Vec::<bool>::from_lox(Literal::List(Shared::new(vec![Literal::Null]))).err(),
+			"Expected a boolean, not null",
+		),
+		(
+			<(f64, f64)>::from_lox(Literal::List(Shared::new(vec![Literal::Number(1.)]))).err(),
+			"Expected a list of 2 items, not [1]",
+		),
+	];
+	for (error, message) in cases {
+		let error = error.unwrap();
+		assert_eq!(ErrorKind::of(&error), ErrorKind::Type);
+		assert_eq!(error.to_string(), message);
+	}
+}
+
+#[test]
+fn integers_stop_at_the_edge_of_their_range() -> Result<()> {
+	let two_to_the = |n| Literal::Number(2f64.powi(n));
+
+	assert!(i64::from_lox(two_to_the(63)).is_err());
+	assert_eq!(i64::from_lox(Literal::Number(-(2f64.powi(63))))?, i64::MIN);
+	assert!(u64::from_lox(two_to_the(64)).is_err());
+	assert_eq!(u64::from_lox(two_to_the(63))?, 1 << 63);
+	assert!(i32::from_lox(two_to_the(31)).is_err());
+	assert_eq!(i32::from_lox(Literal::Number(2147483647.))?, i32::MAX);
+	assert_eq!(u8::from_lox(Literal::Number(255.))?, u8::MAX);
+	Ok(())
+}
@@ -1,50 +1,92 @@
-use std::collections::{btree_map::Entry, BTreeMap};
+use std::{
+	cell::RefCell,
+	collections::{btree_map::Entry, BTreeMap},
+	rc::{Rc, Weak},
+};
 
 use anyhow::{bail, Result};
 
-use super::ast::expr::Literal;
+use super::{ast::expr::Literal, error::ErrorKind};
 
-#[derive(Default, Debug, Clone, PartialEq)]
+/// A scope shared between the interpreter and every closure that captured it
+pub type SharedEnv = Rc<RefCell<Env>>;
+/// A scope that's only looked at while something else keeps it alive
+pub type WeakEnv = Weak<RefCell<Env>>;
+
+#[derive(Default, Debug, PartialEq)]
 pub struct Env {
+	/// Globals, looked up by name since they can be declared after the code that uses them
 	values: BTreeMap<String, Literal>,
-	parent: Option<Box<Env>>,
+	/// Locals, in the order they were declared, which is the slot the resolver gave them
+	slots: Vec<Literal>,
+	parent: Option<SharedEnv>,
 }
 impl Env {
-	pub fn new(parent: Box<Env>) -> Self {
+	pub fn new(parent: SharedEnv) -> Self {
 		Self {
 			values: Default::default(),
+			slots: Default::default(),
 			parent: Some(parent),
 		}
 	}
-	pub fn get(&self, name: String) -> Result<&Literal> {
-		if let Some(value) = self.values.get(&name) {
-			Ok(value)
+	/// Wrap the env so it can be shared
+	pub fn shared(self) -> SharedEnv {
+		Rc::new(RefCell::new(self))
+	}
+	pub fn get(&self, name: &str) -> Result<Literal> {
+		if let Some(value) = self.values.get(name) {
+			Ok(read(value))
 		} else if let Some(parent_env) = &self.parent {
-			parent_env.get(name)
+			parent_env.borrow().get(name)
 		} else {
-			bail!("Undefined variable `{}`", name);
+			bail!(ErrorKind::Name.error(format!("Undefined variable `{}`", name)));
 		}
 	}
-	pub fn get_parent(&self) -> Option<Box<Self>> {
-		self.parent.clone()
-	}
-	pub fn set_parent(&mut self, env: Box<Env>) {
-		self.parent = Some(env);
+	/// Read the local in `slot`, `depth` scopes above this one
+	pub fn get_at(&self, depth: usize, slot: usize) -> Option<Literal> {
+		if depth == 0 {
+			self.slots.get(slot).map(read)
+		} else {
+			self.parent.as_ref()?.borrow().get_at(depth - 1, slot)
+		}
 	}
+	/// Only the global scope has no parent, every other scope stores its declarations in slots
 	pub fn define(&mut self, name: String, value: Literal) {
-		self.values.insert(name, value);
+		if self.parent.is_some() {
+			self.slots.push(value);
+		} else {
+			self.values.insert(name, value);
+		}
 	}
 	pub fn redefine(&mut self, name: String, value: Literal) -> Result<()> {
 		if let Entry::Occupied(mut e) = self.values.entry(name.clone()) {
 			e.insert(value);
 			Ok(())
-		} else if let Some(parent_env) = &mut self.parent {
-			parent_env.redefine(name, value)
+		} else if let Some(parent_env) = &self.parent {
+			parent_env.borrow_mut().redefine(name, value)
+		} else {
+			bail!(ErrorKind::Name.error(format!("{} was not initiated yet", &name)));
+		}
+	}
+	/// Overwrite the local in `slot`, `depth` scopes above this one
+	pub fn redefine_at(&mut self, depth: usize, slot: usize, value: Literal) -> Option<()> {
+		if depth == 0 {
+			*self.slots.get_mut(slot)? = value;
+			Some(())
 		} else {
-			bail!("{} was not initiated yet", &name);
+			self.parent
+				.as_ref()?
+				.borrow_mut()
+				.redefine_at(depth - 1, slot, value)
 		}
 	}
-	pub fn remove(&mut self, key: &str) -> Option<Literal> {
-		self.values.remove(key)
+}
+
+/// A value read out of a scope. Functions are stored in the scope they were declared in
+/// without holding on to it, so the copy that's read does.
+fn read(value: &Literal) -> Literal {
+	match value {
+		Literal::CustomFunction(func) => Literal::CustomFunction(func.upgrade()),
+		value => value.clone(),
 	}
 }
@@ -0,0 +1,193 @@
+use std::{fmt, sync::Arc};
+
+use super::lexer::tokens::token::Span;
+
+/// What sort of mistake an error is, so Lox code that catches it can tell them apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+	/// A value of the wrong type for an operation
+	Type,
+	/// A variable that was never declared
+	Name,
+	/// A call with the wrong number of arguments
+	Arity,
+	/// An index past the end of a list
+	Index,
+	/// A value of the right type that still can't be used, like a zero divisor
+	Value,
+	/// Any other failure in a native function
+	Native,
+	/// Calls nested deeper than the interpreter allows
+	StackOverflow,
+	/// A value bigger than the interpreter allows
+	OutOfMemory,
+	/// Anything that wasn't given a kind
+	Runtime,
+}
+impl ErrorKind {
+	pub fn error(self, message: impl fmt::Display) -> LoxError {
+		LoxError {
+			kind: self,
+			message: message.to_string(),
+		}
+	}
+	/// The kind of any error, `Runtime` for the ones that weren't given one
+	pub fn of(error: &anyhow::Error) -> Self {
+		error
+			.downcast_ref::<LoxError>()
+			.map_or(Self::Runtime, |error| error.kind)
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Type => "TypeError",
+			Self::Name => "NameError",
+			Self::Arity => "ArityError",
+			Self::Index => "IndexError",
+			Self::Value => "ValueError",
+			Self::Native => "NativeError",
+			Self::StackOverflow => "StackOverflowError",
+			Self::OutOfMemory => "OutOfMemoryError",
+			Self::Runtime => "RuntimeError",
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct LoxError {
+	pub kind: ErrorKind,
+	pub message: String,
+}
+impl fmt::Display for LoxError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.message)
+	}
+}
+impl std::error::Error for LoxError {}
+
+/// Everything wrong with a program that was found before running it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+	pub errors: Vec<String>,
+}
+impl fmt::Display for CompileError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.errors.join("\n"))
+	}
+}
+impl std::error::Error for CompileError {}
+
+/// A value thrown by Lox code. Values can't be sent across threads, which every error has to
+/// be, so the interpreter holds on to it until it's caught and the error only refers to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thrown {
+	pub id: usize,
+	/// How the value looks, in case nothing catches it
+	pub description: String,
+	/// Lives as long as the error does, so the interpreter can forget the value once nothing
+	/// can catch it anymore, like when a native drops the error or the host gets it
+	pub(crate) alive: Arc<()>,
+}
+impl fmt::Display for Thrown {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Uncaught {}", self.description)
+	}
+}
+impl std::error::Error for Thrown {}
+
+/// Raised by the `exit` native to stop the whole program with a status code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exit(pub i32);
+impl fmt::Display for Exit {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Exited with code {}", self.0)
+	}
+}
+impl std::error::Error for Exit {}
+
+/// Raised when a program uses up the budget the interpreter gave it. Like [`Exit`] it can't be
+/// caught by Lox code, only by whatever is running the interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupted {
+	/// Evaluated as many statements and expressions as it was allowed to
+	OutOfFuel,
+	/// Still running when its deadline passed
+	DeadlineExceeded,
+}
+impl fmt::Display for Interrupted {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::OutOfFuel => write!(f, "Interrupted: ran out of fuel"),
+			Self::DeadlineExceeded => write!(f, "Interrupted: ran past the deadline"),
+		}
+	}
+}
+impl std::error::Error for Interrupted {}
+
+/// A call that was still running when a [`RuntimeError`] happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+	pub function: String,
+	/// Where it was called from
+	pub span: Span,
+}
+
+/// An error raised while running a program, along with the Lox calls it unwound out of
+#[derive(Debug)]
+pub struct RuntimeError {
+	pub error: anyhow::Error,
+	/// Innermost call first
+	pub trace: Vec<Frame>,
+}
+impl RuntimeError {
+	/// The status code, if the program asked to stop with `exit` rather than failing
+	pub fn exit_code(&self) -> Option<i32> {
+		self.error.downcast_ref::<Exit>().map(|Exit(code)| *code)
+	}
+	/// Why the program was stopped, if it ran out of budget rather than failing
+	pub fn interrupted(&self) -> Option<Interrupted> {
+		self.error.downcast_ref::<Interrupted>().copied()
+	}
+	/// Record that `error` unwound out of a call to `function`
+	pub fn unwind(error: anyhow::Error, function: String, span: Span) -> anyhow::Error {
+		let frame = Frame { function, span };
+		match error.downcast::<Self>() {
+			Ok(mut runtime_error) => {
+				runtime_error.trace.push(frame);
+				runtime_error.into()
+			}
+			Err(error) => Self {
+				error,
+				trace: vec![frame],
+			}
+			.into(),
+		}
+	}
+}
+impl From<anyhow::Error> for RuntimeError {
+	fn from(error: anyhow::Error) -> Self {
+		error.downcast::<Self>().unwrap_or_else(|error| Self {
+			error,
+			trace: Vec::new(),
+		})
+	}
+}
+impl fmt::Display for RuntimeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.error)?;
+		// Deep recursion would repeat the same line over and over, so runs of it are counted
+		let mut frames = self.trace.iter().peekable();
+		while let Some(frame @ Frame { function, span }) = frames.next() {
+			write!(f, "\n    in `{function}`, called at {span}")?;
+
+			let mut repeats = 0;
+			while frames.next_if_eq(&frame).is_some() {
+				repeats += 1;
+			}
+			if repeats > 0 {
+				write!(f, "\n    ... repeated {repeats} more times")?;
+			}
+		}
+		Ok(())
+	}
+}
+impl std::error::Error for RuntimeError {}
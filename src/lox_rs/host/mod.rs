@@ -0,0 +1,97 @@
+use std::{fmt, rc::Rc};
+
+use anyhow::{bail, Result};
+
+use super::{
+	ast::{
+		callables::{callable::Arity, native_fn::NativeFn},
+		expr::Literal,
+		visitors::interp::Interperter,
+	},
+	error::ErrorKind,
+};
+
+/// A Rust value scripts can use as an object, reading and setting its properties and calling
+/// its methods. Scripts share the object and a method can reach it again through Lox, so
+/// everything takes `&self` and anything that changes needs interior mutability.
+pub trait HostObject {
+	/// What the object is called in Lox, when it's printed and in errors
+	fn type_name(&self) -> &str;
+	/// The value of a property, `None` if there's no property with that name
+	fn get(&self, _name: &str) -> Result<Option<Literal>> {
+		Ok(None)
+	}
+	/// Change a property, failing if it doesn't exist or can't be changed
+	fn set(&self, name: &str, _value: Literal) -> Result<()> {
+		bail!(ErrorKind::Name.error(format!(
+			"`{}` has no property `{name}` that can be set",
+			self.type_name()
+		)))
+	}
+	/// How many arguments a method takes, `None` if there's no method with that name
+	fn method(&self, _name: &str) -> Option<Arity> {
+		None
+	}
+	/// Call a method [`HostObject::method`] said exists, with arguments that fit its arity
+	fn call_method(
+		&self,
+		_interp: &mut Interperter,
+		name: &str,
+		_args: Vec<Literal>,
+	) -> Result<Literal> {
+		bail!(ErrorKind::Name.error(format!("`{}` has no method `{name}`", self.type_name())))
+	}
+}
+
+/// A host object as a Lox value. Copies of it are the same object, so it's compared by identity.
+#[derive(Clone)]
+pub struct Host(Rc<dyn HostObject>);
+impl Host {
+	pub fn new(object: impl HostObject + 'static) -> Self {
+		Self(Rc::new(object))
+	}
+	pub fn object(&self) -> &dyn HostObject {
+		&*self.0
+	}
+	/// Look up a property, or a method bound to the object
+	pub fn get(&self, name: &str) -> Result<Literal> {
+		if let Some(value) = self.0.get(name)? {
+			return Ok(value);
+		}
+		let Some(arity) = self.0.method(name) else {
+			bail!(
+				ErrorKind::Name.error(format!("`{}` has no property `{name}`", self.0.type_name()))
+			);
+		};
+
+		let host = self.clone();
+		let method = name.to_string();
+		let name = format!("{}.{name}", self.0.type_name());
+		Ok(Literal::NativeFunction(NativeFn::new(
+			name,
+			arity,
+			move |interp, args| host.0.call_method(interp, &method, args),
+		)))
+	}
+	pub fn set(&self, name: &str, value: Literal) -> Result<()> {
+		self.0.set(name, value)
+	}
+}
+impl fmt::Debug for Host {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("Host").field(&self.0.type_name()).finish()
+	}
+}
+impl fmt::Display for Host {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "<{}>", self.0.type_name())
+	}
+}
+impl PartialEq for Host {
+	fn eq(&self, other: &Self) -> bool {
+		Rc::ptr_eq(&self.0, &other.0)
+	}
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,162 @@
+use std::cell::Cell;
+
+use anyhow::{bail, Result};
+
+use super::{
+	super::{
+		ast::{
+			callables::callable::Arity,
+			expr::{Literal, Shared},
+			visitors::interp::Interperter,
+		},
+		convert::{FromLox, IntoLox},
+		error::ErrorKind,
+		lox::Lox,
+	},
+	Host,
+	HostObject,
+};
+
+/// A counter scripts can read, reset and step through
+struct Counter {
+	count: Cell<f64>,
+}
+impl HostObject for Counter {
+	fn type_name(&self) -> &str {
+		"Counter"
+	}
+	fn get(&self, name: &str) -> Result<Option<Literal>> {
+		Ok((name == "count").then(|| Literal::Number(self.count.get())))
+	}
+	fn set(&self, name: &str, value: Literal) -> Result<()> {
+		match name {
+			"count" => self.count.set(f64::from_lox(value)?),
+			_ => bail!(ErrorKind::Name.error(format!("`Counter` has no property `{name}`"))),
+		}
+		Ok(())
+	}
+	fn method(&self, name: &str) -> Option<Arity> {
+		match name {
+			"step" => Some(0.into()),
+			"each" => Some(1.into()),
+			_ => None,
+		}
+	}
+	fn call_method(
+		&self,
+		interp: &mut Interperter,
+		name: &str,
+		args: Vec<Literal>,
+	) -> Result<Literal> {
+		match name {
+			"step" => {
+				self.count.set(self.count.get() + 1.);
+				Ok(Literal::Number(self.count.get()))
+			}
+			// Calls back into Lox with every number up to the count
+			"each" => {
+				for i in 0..self.count.get() as usize {
+					interp.call(&args[0], vec![i.into_lox()])?;
+				}
+				Ok(Literal::Null)
+			}
+			_ => unreachable!(),
+		}
+	}
+}
+
+fn counter() -> Host {
+	Host::new(Counter {
+		count: Cell::new(0.),
+	})
+}
+
+#[test]
+fn properties_can_be_read_and_set() -> Result<()> {
+	let mut lox = Lox::new();
+	lox.set_global("counter", counter().into_lox());
+
+	assert_eq!(lox.eval("counter.count;")?, Literal::Number(0.));
+	assert_eq!(lox.eval("counter.count = 5;")?, Literal::Number(5.));
+	assert_eq!(lox.eval("counter.count + 1;")?, Literal::Number(6.));
+	assert_eq!(
+		lox.eval("counter.count += 2; counter.count++; counter.count;")?,
+		Literal::Number(8.)
+	);
+	assert_eq!(
+		lox.eval("try { counter.size; } catch (e) { [e['kind'], e['message']]; }")?,
+		lox.eval("['NameError', '`Counter` has no property `size`'];")?
+	);
+	assert_eq!(
+		lox.eval("try { counter.count = 'a'; } catch (e) { e['kind']; }")?,
+		Literal::String(Shared::new("TypeError".to_string()))
+	);
+	assert_eq!(lox.eval("counter;")?.to_string(), "<Counter>");
+	Ok(())
+}
+
+#[test]
+fn methods_are_bound_to_the_object() -> Result<()> {
+	let mut lox = Lox::new();
+	lox.set_global("counter", counter().into_lox());
+
+	assert_eq!(
+		lox.eval("counter.step(); counter.step();")?,
+		Literal::Number(2.)
+	);
+	// Methods can't be assigned to
+	assert_eq!(
+		lox.eval("try { counter.step = 1; } catch (e) { e['kind']; }")?,
+		Literal::String(Shared::new("NameError".to_string()))
+	);
+	// A method keeps its object after it's taken off of it
+	assert_eq!(
+		lox.eval("var step = counter.step; step();")?,
+		Literal::Number(3.)
+	);
+	assert_eq!(
+		lox.eval("var seen = 0; fn see(i) { seen = seen * 10 + i + 1; } counter.each(see); seen;")?,
+		Literal::Number(123.)
+	);
+
+	let err = lox.eval("counter.step(1);").unwrap_err();
+	assert_eq!(
+		err.to_string(),
+		"`Counter.step` expected 0 arguments but got 1"
+	);
+	Ok(())
+}
+
+#[test]
+fn copies_are_the_same_object() -> Result<()> {
+	let mut lox = Lox::new();
+	let counter = counter();
+	lox.set_global("a", counter.clone().into_lox());
+	lox.set_global("b", counter.into_lox());
+	lox.set_global("c", self::counter().into_lox());
+
+	assert_eq!(lox.eval("a.step(); b.count;")?, Literal::Number(1.));
+	assert_eq!(lox.eval("[a == b, a == c];")?, lox.eval("[true, false];")?);
+	Ok(())
+}
+
+#[test]
+fn optional_chains_stop_at_null() -> Result<()> {
+	let mut lox = Lox::new();
+	lox.set_global("counter", counter().into_lox());
+	lox.eval("var config = {'name': 'lox', 'inner': null};")?;
+
+	assert_eq!(lox.eval("config.name;")?, lox.eval("'lox';")?);
+	assert_eq!(lox.eval("config.missing;")?, Literal::Null);
+	// The rest of the chain is skipped, so nothing tries to read a property of `null`
+	assert_eq!(lox.eval("config.inner?.a.b(1)[2];")?, Literal::Null);
+	assert_eq!(lox.eval("config?.name;")?, lox.eval("'lox';")?);
+	assert_eq!(lox.eval("counter?.step();")?, Literal::Number(1.));
+
+	let err = lox.eval("config.inner.a;").unwrap_err();
+	assert_eq!(
+		err.to_string(),
+		"Only maps and host objects have properties, not null"
+	);
+	Ok(())
+}
@@ -2,8 +2,18 @@ use anyhow::Result;
 use nom::{
 	branch::alt,
 	bytes::complete::{tag, take_until},
-	character::complete::{alpha1, alphanumeric1, anychar, char, digit1, line_ending, space1, tab},
-	combinator::{eof, map, map_res, recognize, value},
+	character::complete::{
+		alpha1,
+		alphanumeric1,
+		anychar,
+		char,
+		digit1,
+		line_ending,
+		satisfy,
+		space1,
+		tab,
+	},
+	combinator::{eof, map, map_res, not, recognize, value},
 	multi::{many0, many1, many_m_n},
 	sequence::{delimited, terminated, tuple},
 	IResult,
@@ -17,12 +27,16 @@ pub fn detect_punctuation(input: &[u8]) -> IResult<&[u8], Punctuation> {
 		value(Punctuation::BracketClose, tag(b")")),
 		value(Punctuation::BracketCurlyOpen, tag(b"{")),
 		value(Punctuation::BracketCurlyClose, tag(b"}")),
+		value(Punctuation::BracketSquareOpen, tag(b"[")),
+		value(Punctuation::BracketSquareClose, tag(b"]")),
 		value(Punctuation::QuoteSingle, tag(b"'")),
 		value(Punctuation::QuoteDouble, tag(b"\"")),
 		value(Punctuation::Semicolon, tag(b";")),
 		value(Punctuation::Colon, tag(b":")),
+		value(Punctuation::Question, tag(b"?")),
 		value(Punctuation::Pipe, tag(b"|")),
 		value(Punctuation::Ampersand, tag(b"&")),
+		value(Punctuation::Ellipsis, tag(b"...")),
 		value(Punctuation::Dot, tag(b".")),
 		value(Punctuation::Comma, tag(b",")),
 		value(Punctuation::Space, space1),
@@ -33,38 +47,75 @@ pub fn detect_punctuation(input: &[u8]) -> IResult<&[u8], Punctuation> {
 
 pub fn detect_operator(input: &[u8]) -> IResult<&[u8], Operator> {
 	let (tail, op) = alt((
-		value(Operator::NotEq, tag(b"!=")),
-		value(Operator::EqEq, tag(b"==")),
-		value(Operator::Gte, tag(b">=")),
-		value(Operator::Lte, tag(b"<=")),
-		value(Operator::Gt, tag(b">")),
-		value(Operator::Lt, tag(b"<")),
-		value(Operator::Eq, tag(b"=")),
-		value(Operator::Not, tag(b"!")),
-		value(Operator::Add, tag(b"+")),
-		value(Operator::Sub, tag(b"-")),
-		value(Operator::Mul, tag(b"*")),
-		value(Operator::Div, tag(b"/")),
-		value(Operator::And, tag(b"&&")),
-		value(Operator::Or, tag(b"||")),
+		alt((
+			value(Operator::ShiftLeft, tag(b"<<")),
+			value(Operator::ShiftRight, tag(b">>")),
+			value(Operator::NotEq, tag(b"!=")),
+			value(Operator::EqEq, tag(b"==")),
+			value(Operator::Arrow, tag(b"=>")),
+			value(Operator::Gte, tag(b">=")),
+			value(Operator::Lte, tag(b"<=")),
+			value(Operator::AddEq, tag(b"+=")),
+			value(Operator::SubEq, tag(b"-=")),
+			value(Operator::MulEq, tag(b"*=")),
+			value(Operator::DivEq, tag(b"/=")),
+			value(Operator::ModEq, tag(b"%=")),
+			value(Operator::Increment, tag(b"++")),
+			value(Operator::Decrement, tag(b"--")),
+			value(Operator::Pow, tag(b"**")),
+			value(Operator::FloorDiv, tag(b"//")),
+			value(Operator::And, tag(b"&&")),
+			value(Operator::Or, tag(b"||")),
+			value(Operator::Pipeline, tag(b"|>")),
+			value(Operator::Coalesce, tag(b"??")),
+			value(Operator::OptionalChain, tag(b"?.")),
+		)),
+		alt((
+			value(Operator::Gt, tag(b">")),
+			value(Operator::Lt, tag(b"<")),
+			value(Operator::Eq, tag(b"=")),
+			value(Operator::Not, tag(b"!")),
+			value(Operator::Add, tag(b"+")),
+			value(Operator::Sub, tag(b"-")),
+			value(Operator::Mul, tag(b"*")),
+			value(Operator::Div, tag(b"/")),
+			value(Operator::Mod, tag(b"%")),
+			value(Operator::BitXor, tag(b"^")),
+			value(Operator::BitNot, tag(b"~")),
+		)),
 	))(input)?;
 	Ok((tail, op))
 }
 
+/// Make sure a word isn't just the prefix of a longer identifier (`format` shouldn't be `for`)
+fn word_end(input: &[u8]) -> IResult<&[u8], ()> {
+	not(satisfy(|c| c.is_alphanumeric() || c == '_' || c == '$'))(input)
+}
+
 pub fn detect_keyword(input: &[u8]) -> IResult<&[u8], Keyword> {
-	let (tail, kw) = alt((
-		value(Keyword::Class, tag(b"class")),
-		value(Keyword::If, tag(b"if")),
-		value(Keyword::Else, tag(b"else")),
-		value(Keyword::Function, tag(b"fn")),
-		value(Keyword::For, tag(b"for")),
-		value(Keyword::While, tag(b"while")),
-		value(Keyword::Print, tag(b"print")),
-		value(Keyword::Return, tag(b"return")),
-		value(Keyword::Super, tag(b"super")),
-		value(Keyword::This, tag(b"this")),
-		value(Keyword::Var, tag(b"var")),
-	))(input)?;
+	let (tail, kw) = terminated(
+		alt((
+			value(Keyword::Class, tag(b"class")),
+			value(Keyword::If, tag(b"if")),
+			value(Keyword::Else, tag(b"else")),
+			value(Keyword::Function, tag(b"fn")),
+			value(Keyword::For, tag(b"for")),
+			value(Keyword::While, tag(b"while")),
+			value(Keyword::Print, tag(b"print")),
+			value(Keyword::Return, tag(b"return")),
+			value(Keyword::Super, tag(b"super")),
+			value(Keyword::This, tag(b"this")),
+			value(Keyword::Var, tag(b"var")),
+			value(Keyword::Match, tag(b"match")),
+			value(Keyword::Break, tag(b"break")),
+			value(Keyword::Continue, tag(b"continue")),
+			value(Keyword::Throw, tag(b"throw")),
+			value(Keyword::Try, tag(b"try")),
+			value(Keyword::Catch, tag(b"catch")),
+			value(Keyword::Finally, tag(b"finally")),
+		)),
+		word_end,
+	)(input)?;
 	Ok((tail, kw))
 }
 
@@ -102,13 +153,13 @@ pub fn detect_string(input: &[u8]) -> IResult<&[u8], &[u8]> {
 	Ok((tail, token))
 }
 
-pub fn detect_literal(input: &[u8]) -> IResult<&[u8], Literal> {
+pub fn detect_literal(input: &[u8]) -> IResult<&[u8], Literal<'_>> {
 	let (tail, token) = alt((
 		map(detect_decimal, Literal::Number),
 		map(detect_string, Literal::String),
-		value(Literal::Boolean(true), tag("true")),
-		value(Literal::Boolean(false), tag("false")),
-		value(Literal::Null, tag("null")),
+		value(Literal::Boolean(true), terminated(tag("true"), word_end)),
+		value(Literal::Boolean(false), terminated(tag("false"), word_end)),
+		value(Literal::Null, terminated(tag("null"), word_end)),
 	))(input)?;
 	Ok((tail, token))
 }
@@ -121,7 +172,7 @@ pub fn detect_identifier(input: &[u8]) -> IResult<&[u8], &[u8]> {
 	Ok((tail, token))
 }
 
-pub fn detect_ends(input: &[u8]) -> IResult<&[u8], TokenType> {
+pub fn detect_ends(input: &[u8]) -> IResult<&[u8], TokenType<'_>> {
 	let (tail, kw) = alt((
 		value(TokenType::EndOfFile, eof),
 		value(TokenType::EndOfLine, line_ending),
@@ -129,7 +180,7 @@ pub fn detect_ends(input: &[u8]) -> IResult<&[u8], TokenType> {
 	Ok((tail, kw))
 }
 
-pub fn detect(input: &[u8]) -> IResult<&[u8], TokenType> {
+pub fn detect(input: &[u8]) -> IResult<&[u8], TokenType<'_>> {
 	let (tail, token) = alt((
 		map(detect_keyword, TokenType::Keyword),
 		map(detect_operator, TokenType::Operator),
@@ -13,12 +13,23 @@ use super::{
 pub fn scan(mut input: &[u8]) -> Box<Peekable<impl Iterator<Item = Token<'_>>>> {
 	Box::new(
 		iter::from_fn({
+			let (mut line, mut column) = (1, 1);
 			move || match detect(input) {
 				Ok((tail, token_type)) => {
+					let token = Token::new(token_type, line, column);
+					for &byte in &input[..input.len() - tail.len()] {
+						if byte == b'\n' {
+							line += 1;
+							column = 1;
+						} else if byte & 0b1100_0000 != 0b1000_0000 {
+							// Count characters, not the continuation bytes of UTF-8
+							column += 1;
+						}
+					}
 					input = tail;
 
-					if token_type != TokenType::EndOfFile {
-						Some(Token::new(token_type, 0, 0))
+					if token.get() != &TokenType::EndOfFile {
+						Some(token)
 					} else {
 						None
 					}
@@ -15,7 +15,7 @@ fn sanity() -> Result<()> {
 
 	assert_eq!(
 		input,
-		vec![Token::new(TokenType::Literal(Literal::Null), 0, 0)]
+		vec![Token::new(TokenType::Literal(Literal::Null), 1, 1)]
 	);
 	Ok(())
 }
@@ -28,9 +28,47 @@ fn one_plus_one() -> Result<()> {
 	assert_eq!(
 		input,
 		vec![
-			Token::new(TokenType::Literal(Literal::Number(1.)), 0, 0),
-			Token::new(TokenType::Operator(Operator::Add), 0, 0),
-			Token::new(TokenType::Literal(Literal::Number(1.)), 0, 0),
+			Token::new(TokenType::Literal(Literal::Number(1.)), 1, 1),
+			Token::new(TokenType::Operator(Operator::Add), 1, 3),
+			Token::new(TokenType::Literal(Literal::Number(1.)), 1, 5),
+		]
+	);
+	Ok(())
+}
+
+#[test]
+fn keywords_need_a_word_boundary() -> Result<()> {
+	let input = b"format nullable";
+	let input: Vec<Token> = scan(input).collect();
+
+	assert_eq!(
+		input,
+		vec![
+			Token::new(TokenType::Identifier(b"format"), 1, 1),
+			Token::new(TokenType::Identifier(b"nullable"), 1, 8),
+		]
+	);
+	Ok(())
+}
+
+#[test]
+fn tokens_know_where_they_start() -> Result<()> {
+	let input = "var s = 'é\nb';\n  print s;".as_bytes();
+	let spans: Vec<_> = scan(input)
+		.map(|t| (t.span().line, t.span().column))
+		.collect();
+
+	assert_eq!(
+		spans,
+		vec![
+			(1, 1),
+			(1, 5),
+			(1, 7),
+			(1, 9),
+			(2, 3),
+			(3, 3),
+			(3, 9),
+			(3, 10)
 		]
 	);
 	Ok(())
@@ -2,6 +2,18 @@ use std::fmt;
 
 use super::token_type::TokenType;
 
+/// Where something starts in the source, lines and columns both count from 1
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+	pub line: usize,
+	pub column: usize,
+}
+impl fmt::Display for Span {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "line {}, column {}", self.line, self.column)
+	}
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Token<'t> {
 	token_type: TokenType<'t>,
@@ -20,6 +32,12 @@ impl<'t> Token<'t> {
 	pub fn get(&self) -> &TokenType<'t> {
 		&self.token_type
 	}
+	pub fn span(&self) -> Span {
+		Span {
+			line: self.line,
+			column: self.column,
+		}
+	}
 }
 impl<'t> fmt::Display for Token<'t> {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -20,6 +20,10 @@ pub enum Punctuation {
 	BracketCurlyOpen,
 	/// `}`
 	BracketCurlyClose,
+	/// `[`
+	BracketSquareOpen,
+	/// `]`
+	BracketSquareClose,
 	/// `'`
 	QuoteSingle,
 	/// `"`
@@ -28,10 +32,14 @@ pub enum Punctuation {
 	Semicolon,
 	/// `:`
 	Colon,
+	/// `?`
+	Question,
 	/// `|`
 	Pipe,
 	/// `&`
 	Ampersand,
+	/// `...`
+	Ellipsis,
 	/// `.`
 	Dot,
 	/// `,`
@@ -68,10 +76,50 @@ pub enum Operator {
 	Mul,
 	/// /
 	Div,
+	/// %
+	Mod,
+	/// **
+	Pow,
+	/// //
+	FloorDiv,
+	/// +=
+	AddEq,
+	/// -=
+	SubEq,
+	/// *=
+	MulEq,
+	/// /=
+	DivEq,
+	/// %=
+	ModEq,
+	/// ++
+	Increment,
+	/// --
+	Decrement,
 	/// &&
 	And,
 	/// ||
 	Or,
+	/// ??
+	Coalesce,
+	/// ?.
+	OptionalChain,
+	/// & (lexed as [`Punctuation::Ampersand`])
+	BitAnd,
+	/// | (lexed as [`Punctuation::Pipe`])
+	BitOr,
+	/// ^
+	BitXor,
+	/// ~
+	BitNot,
+	/// <<
+	ShiftLeft,
+	/// >>
+	ShiftRight,
+	/// |>
+	Pipeline,
+	/// => (separates a `match` arm's pattern from its body)
+	Arrow,
 }
 impl Operator {
 	pub fn to_str(&self) -> &str {
@@ -88,8 +136,39 @@ impl Operator {
 			Self::Sub => "-",
 			Self::Mul => "*",
 			Self::Div => "/",
+			Self::Mod => "%",
+			Self::Pow => "**",
+			Self::FloorDiv => "//",
+			Self::AddEq => "+=",
+			Self::SubEq => "-=",
+			Self::MulEq => "*=",
+			Self::DivEq => "/=",
+			Self::ModEq => "%=",
+			Self::Increment => "++",
+			Self::Decrement => "--",
 			Operator::And => "&&",
 			Operator::Or => "||",
+			Operator::Coalesce => "??",
+			Operator::OptionalChain => "?.",
+			Operator::BitAnd => "&",
+			Operator::BitOr => "|",
+			Operator::BitXor => "^",
+			Operator::BitNot => "~",
+			Operator::ShiftLeft => "<<",
+			Operator::ShiftRight => ">>",
+			Operator::Pipeline => "|>",
+			Operator::Arrow => "=>",
+		}
+	}
+	/// The arithmetic operator applied by a compound assignment or an increment/decrement
+	pub fn to_binary(&self) -> Option<Self> {
+		match self {
+			Self::AddEq | Self::Increment => Some(Self::Add),
+			Self::SubEq | Self::Decrement => Some(Self::Sub),
+			Self::MulEq => Some(Self::Mul),
+			Self::DivEq => Some(Self::Div),
+			Self::ModEq => Some(Self::Mod),
+			_ => None,
 		}
 	}
 }
@@ -107,6 +186,13 @@ pub enum Keyword {
 	Super,
 	This,
 	Var,
+	Match,
+	Break,
+	Continue,
+	Throw,
+	Try,
+	Catch,
+	Finally,
 }
 
 #[derive(Debug, Clone, PartialEq)]
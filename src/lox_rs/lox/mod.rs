@@ -0,0 +1,118 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Result};
+
+use super::{
+	ast::{
+		callables::{module::NativeModule, native_fn::NativeFn},
+		expr::Literal,
+		stmt::Stmt,
+		visitors::{interp::Interperter, resolver::resolve},
+	},
+	error::{CompileError, RuntimeError},
+	lexer::scanner::scan,
+	output::Output,
+	parser::{Parser, DEFAULT_MAX_DEPTH},
+};
+
+/// Runs Lox programs for a host. The globals they declare stay around between runs, so the host
+/// can call the functions they define and read what they left behind.
+#[derive(Debug)]
+pub struct Lox {
+	interp: Interperter,
+	max_nesting_depth: usize,
+}
+impl Lox {
+	pub fn new() -> Self {
+		Self {
+			interp: Interperter::default(),
+			max_nesting_depth: DEFAULT_MAX_DEPTH,
+		}
+	}
+	/// How deeply programs can nest expressions and statements in each other
+	pub fn max_nesting_depth(mut self, max_nesting_depth: usize) -> Self {
+		self.max_nesting_depth = max_nesting_depth;
+		self
+	}
+	/// Where programs `print` to and where warnings go, instead of stdout and stderr
+	pub fn output(mut self, output: Output) -> Self {
+		self.interp.output = output;
+		self
+	}
+	/// The interpreter running the programs, to set its limits
+	pub fn interpreter(&mut self) -> &mut Interperter {
+		&mut self.interp
+	}
+
+	/// Parse and resolve a program without running it, failing with a [`CompileError`] that
+	/// lists every mistake found
+	pub fn compile(&self, source: &str) -> Result<Vec<Stmt>> {
+		let tokens = scan(source.as_bytes());
+
+		let mut parser = Parser::new(tokens).max_depth(self.max_nesting_depth);
+		let (mut program, errors) = parser.parse()?;
+
+		let mut errors = errors.to_vec();
+		errors.extend(resolve(&mut program));
+		if !errors.is_empty() {
+			bail!(CompileError { errors });
+		}
+		Ok(program)
+	}
+	/// Run a compiled program up to the first statement that fails, returning the value of the
+	/// last one
+	pub fn run(&mut self, program: &[Stmt]) -> Result<Literal, RuntimeError> {
+		let mut value = Literal::Null;
+		for stmt in program {
+			value = stmt.interpret(&mut self.interp)?;
+		}
+		Ok(value)
+	}
+	/// Compile and run a program, failing with either a [`CompileError`] or a [`RuntimeError`]
+	pub fn eval(&mut self, source: &str) -> Result<Literal> {
+		let program = self.compile(source)?;
+		Ok(self.run(&program)?)
+	}
+	pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<Literal> {
+		let source = fs::read_to_string(path)?;
+		self.eval(&source)
+	}
+
+	/// Let programs call a Rust function, see [`NativeFn::new`]
+	pub fn register_native(&mut self, native: NativeFn) {
+		self.interp.register_native(native);
+	}
+	/// Let programs call a group of Rust functions, through a global map named after the module
+	pub fn register_module(&mut self, module: &NativeModule) {
+		self.interp.register_module(module);
+	}
+
+	pub fn set_global(&mut self, name: &str, value: Literal) {
+		self.interp
+			.global
+			.borrow_mut()
+			.define(name.to_string(), value);
+	}
+	pub fn get_global(&self, name: &str) -> Option<Literal> {
+		self.interp.global.borrow().get(name).ok()
+	}
+	/// Call a global function with arguments from the host
+	pub fn call_function(
+		&mut self,
+		name: &str,
+		args: Vec<Literal>,
+	) -> Result<Literal, RuntimeError> {
+		let function = self.interp.global.borrow().get(name);
+		function
+			.and_then(|function| self.interp.call(&function, args))
+			.map_err(RuntimeError::from)
+	}
+}
+impl Default for Lox {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests;
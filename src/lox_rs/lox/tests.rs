@@ -0,0 +1,270 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{bail, Result};
+
+use super::{
+	super::{
+		ast::{
+			callables::{callable::Arity, module::NativeModule, native_fn::NativeFn},
+			expr::{Literal, Shared},
+		},
+		error::{CompileError, RuntimeError},
+		host::{Host, HostObject},
+		output::{Buffer, Output},
+	},
+	Lox,
+};
+
+#[test]
+fn eval_returns_the_last_value() -> Result<()> {
+	let mut lox = Lox::new();
+	assert_eq!(lox.eval("var a = 1; a + 1;")?, Literal::Number(2.));
+	// Globals stay around for the next program
+	assert_eq!(lox.eval("a * 10;")?, Literal::Number(10.));
+	Ok(())
+}
+
+#[test]
+fn globals_can_be_shared_with_the_host() -> Result<()> {
+	let mut lox = Lox::new();
+	lox.set_global(
+		"greeting",
+		Literal::String(Shared::new("hello".to_string())),
+	);
+	lox.eval("var shout = greeting + '!';")?;
+
+	assert_eq!(
+		lox.get_global("shout"),
+		Some(Literal::String(Shared::new("hello!".to_string())))
+	);
+	assert_eq!(lox.get_global("missing"), None);
+	Ok(())
+}
+
+#[test]
+fn functions_can_be_called_by_the_host() -> Result<()> {
+	let mut lox = Lox::new();
+	lox.eval("fn add(a, b) { return a + b; }")?;
+
+	assert_eq!(
+		lox.call_function("add", vec![Literal::Number(1.), Literal::Number(2.)])?,
+		Literal::Number(3.)
+	);
+
+	let err = lox
+		.call_function("add", vec![Literal::Number(1.)])
+		.unwrap_err();
+	assert_eq!(err.to_string(), "`add` expected 2 arguments but got 1");
+	let err = lox.call_function("missing", Vec::new()).unwrap_err();
+	assert_eq!(err.to_string(), "Undefined variable `missing`");
+	Ok(())
+}
+
+#[test]
+fn errors_say_when_they_happened() -> Result<()> {
+	let mut lox = Lox::new();
+
+	let err = lox.eval("print ; return 1;").unwrap_err();
+	let errors = &err.downcast_ref::<CompileError>().unwrap().errors;
+	assert_eq!(errors.len(), 2, "{errors:?}");
+
+	let err = lox.eval("fn f() { return 1 < 'a'; } f();").unwrap_err();
+	assert_eq!(err.downcast_ref::<RuntimeError>().unwrap().trace.len(), 1);
+	Ok(())
+}
+
+#[test]
+fn natives_can_capture_host_state() -> Result<()> {
+	let mut lox = Lox::new();
+	let logged = Rc::new(RefCell::new(Vec::new()));
+	let log = logged.clone();
+	lox.register_native(NativeFn::new("log", Arity::at_least(1), move |_, args| {
+		log.borrow_mut().extend(args);
+		Ok(Literal::Null)
+	}));
+
+	lox.eval("log(1); log('a', 'b');")?;
+	assert_eq!(
+		*logged.borrow(),
+		[
+			Literal::Number(1.),
+			Literal::String(Shared::new("a".to_string())),
+			Literal::String(Shared::new("b".to_string()))
+		]
+	);
+	Ok(())
+}
+
+#[test]
+fn natives_can_call_back_into_lox() -> Result<()> {
+	let mut lox = Lox::new();
+	lox.register_native(NativeFn::new("twice", 2, |interp, args| {
+		let [callback, value] = &args[..] else {
+			bail!("`twice` takes a callback and a value");
+		};
+		let once = interp.call(callback, vec![value.clone()])?;
+		interp.call(callback, vec![once])
+	}));
+
+	assert_eq!(
+		lox.eval("fn double(n) { return n * 2; } twice(double, 3);")?,
+		Literal::Number(12.)
+	);
+	// What the callback throws unwinds through the native untouched
+	assert_eq!(
+		lox.eval("fn fail(n) { throw n; } try { twice(fail, 'thrown'); } catch (e) { e; }")?,
+		Literal::String(Shared::new("thrown".to_string()))
+	);
+	Ok(())
+}
+
+/// A host object that counts how many references to `Rc<()>` are still alive, to tell when
+/// the interpreter let go of it
+struct Token {
+	_count: Rc<()>,
+}
+impl HostObject for Token {
+	fn type_name(&self) -> &str {
+		"Token"
+	}
+}
+
+#[test]
+fn thrown_values_nothing_can_catch_are_dropped() -> Result<()> {
+	let token = Rc::new(());
+	let mut lox = Lox::new();
+	lox.register_native(NativeFn::new("ignore", 1, |interp, args| {
+		let _ = interp.call(&args[0], vec![]);
+		Ok(Literal::Null)
+	}));
+	lox.set_global(
+		"token",
+		Literal::HostObject(Host::new(Token {
+			_count: Rc::clone(&token),
+		})),
+	);
+	lox.eval("fn fail() { throw token; }")?;
+
+	// Once the native or the host drops the error, the value is forgotten by the next throw
+	lox.eval("ignore(fail);")?;
+	assert!(lox.call_function("fail", vec![]).is_err());
+	lox.eval("token = null; try { throw 1; } catch (e) {}")?;
+	assert_eq!(Rc::strong_count(&token), 1);
+	Ok(())
+}
+
+#[test]
+fn scopes_are_dropped_with_the_functions_declared_in_them() -> Result<()> {
+	let token = Rc::new(());
+	let mut lox = Lox::new();
+	lox.set_global(
+		"token",
+		Literal::HostObject(Host::new(Token {
+			_count: Rc::clone(&token),
+		})),
+	);
+
+	lox.eval(
+		"for (var i = 0; i < 3; i = i + 1) { var t = token; fn f() { return t; } f(); }
+		fn make() { var t = token; fn get() { return t; } return get; }
+		var get = make();",
+	)?;
+	// Functions that were returned out of their scope still hold on to it
+	assert_eq!(Rc::strong_count(&token), 2);
+	assert!(matches!(lox.eval("get();")?, Literal::HostObject(_)));
+	lox.eval("token = null; get = null;")?;
+	assert_eq!(Rc::strong_count(&token), 1);
+	Ok(())
+}
+
+#[test]
+fn scopes_functions_keep_alive_are_dropped_with_the_interpreter() -> Result<()> {
+	let token = Rc::new(());
+	let mut lox = Lox::new();
+	lox.set_global(
+		"token",
+		Literal::HostObject(Host::new(Token {
+			_count: Rc::clone(&token),
+		})),
+	);
+
+	// Each of these functions is stored somewhere it can reach its own scope from
+	lox.eval(
+		"fn f() { return token; }
+		var g = f;
+		var callbacks = [];
+		fn keep() { var t = token; fn get() { return t; } callbacks = [get]; }
+		keep();
+		var outer;
+		{ var t = token; fn h() { return t; } outer = h; }",
+	)?;
+	assert!(matches!(lox.eval("outer();")?, Literal::HostObject(_)));
+	drop(lox);
+	assert_eq!(Rc::strong_count(&token), 1);
+	Ok(())
+}
+
+#[test]
+fn modules_group_natives() -> Result<()> {
+	let mut lox = Lox::new();
+	let math = NativeModule::new("math")
+		.function("max", 2, |_, args| match args[..] {
+			[Literal::Number(a), Literal::Number(b)] => Ok(Literal::Number(a.max(b))),
+			_ => bail!("Please only compare numbers"),
+		})
+		.function("pi", 0, |_, _| Ok(Literal::Number(std::f64::consts::PI)));
+	lox.register_module(&math);
+
+	assert_eq!(lox.eval("math['max'](1, 2);")?, Literal::Number(2.));
+	assert_eq!(lox.eval("math.max(3, 4);")?, Literal::Number(4.));
+	assert_eq!(
+		lox.eval("try { math['max'](1, 'a'); } catch (e) { [e['kind'], e['message']]; }")?,
+		lox.eval("['NativeError', 'Please only compare numbers'];")?
+	);
+	Ok(())
+}
+
+#[test]
+fn typed_natives_convert_their_arguments() -> Result<()> {
+	let mut lox = Lox::new();
+	lox.register_native(NativeFn::typed(
+		"is_longer",
+		|len: f64, s: String| -> Result<bool> { Ok(s.len() as f64 > len) },
+	));
+	lox.register_module(
+		&NativeModule::new("lists").typed("sum", |items: Vec<f64>| -> Result<f64> {
+			Ok(items.iter().sum())
+		}),
+	);
+
+	assert_eq!(lox.eval("is_longer(2, 'abc');")?, Literal::Boolean(true));
+	assert_eq!(lox.eval("lists['sum']([1, 2, 3]);")?, Literal::Number(6.));
+	assert_eq!(
+		lox.eval("try { is_longer('2', 'abc'); } catch (e) { [e['kind'], e['message']]; }")?,
+		lox.eval("['TypeError', 'Argument 1 of `is_longer`: Expected a number, not \"2\"'];")?
+	);
+	let err = lox.eval("is_longer(1);").unwrap_err();
+	assert_eq!(
+		err.to_string(),
+		"`is_longer` expected 2 arguments but got 1"
+	);
+	Ok(())
+}
+
+#[test]
+fn output_goes_where_the_host_wants() -> Result<()> {
+	let (printed, warnings) = (Buffer::new(), Buffer::new());
+	let mut lox = Lox::new().output(Output::new(printed.clone(), warnings.clone()));
+	lox.register_native(NativeFn::new("old", 0, |interp, _| {
+		interp.output.warn("`old` is deprecated")?;
+		Ok(Literal::Null)
+	}));
+
+	lox.eval("print 'hello'; old(); print 1 + 1;")?;
+	assert_eq!(printed.take(), "\"hello\"\n2\n");
+	assert_eq!(warnings.take(), "Warning: `old` is deprecated\n");
+	// Taking the output empties the buffer
+	lox.eval("print null;")?;
+	assert_eq!(printed.take(), "null\n");
+	Ok(())
+}
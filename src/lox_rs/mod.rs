@@ -1,4 +1,10 @@
 pub mod ast;
+pub mod convert;
 pub mod env;
+pub mod error;
+pub mod host;
 pub mod lexer;
+pub mod lox;
+pub mod output;
 pub mod parser;
+pub mod stack;
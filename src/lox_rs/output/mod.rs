@@ -0,0 +1,68 @@
+use std::{
+	cell::RefCell,
+	fmt,
+	io::{self, Write},
+	rc::Rc,
+};
+
+use anyhow::Result;
+
+use super::ast::expr::Literal;
+
+/// Where the interpreter writes what programs `print`, and the warnings it has for the host.
+/// By default that's stdout and stderr.
+pub struct Output {
+	print: Box<dyn Write>,
+	warnings: Box<dyn Write>,
+}
+impl Output {
+	pub fn new(print: impl Write + 'static, warnings: impl Write + 'static) -> Self {
+		Self {
+			print: Box::new(print),
+			warnings: Box::new(warnings),
+		}
+	}
+	/// Write a value on its own line, like the `print` statement does
+	pub fn print(&mut self, value: &Literal) -> Result<()> {
+		writeln!(self.print, "{value}")?;
+		Ok(())
+	}
+	pub fn warn(&mut self, message: impl fmt::Display) -> Result<()> {
+		writeln!(self.warnings, "Warning: {message}")?;
+		Ok(())
+	}
+}
+impl Default for Output {
+	fn default() -> Self {
+		Self::new(io::stdout(), io::stderr())
+	}
+}
+impl fmt::Debug for Output {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Output").finish_non_exhaustive()
+	}
+}
+
+/// Text written to memory instead of a stream. Clones share it, so the host can keep one to
+/// read what the program wrote to the other.
+#[derive(Debug, Clone, Default)]
+pub struct Buffer(Rc<RefCell<Vec<u8>>>);
+impl Buffer {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	/// Everything written so far, leaving the buffer empty
+	pub fn take(&self) -> String {
+		let bytes = self.0.take();
+		String::from_utf8_lossy(&bytes).into_owned()
+	}
+}
+impl Write for Buffer {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.borrow_mut().extend_from_slice(buf);
+		Ok(buf.len())
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
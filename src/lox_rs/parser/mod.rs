@@ -1,22 +1,30 @@
-use std::iter::Peekable;
+use std::{iter::Peekable, mem, rc::Rc};
 
 use anyhow::{anyhow, bail, Ok, Result};
 
 use super::{
 	ast::{
-		expr::{Expr, Literal},
+		expr::{Expr, Literal, Shared, Target, Var},
+		pattern::{MatchArm, Params, Pattern, PatternElement},
 		stmt::Stmt,
 	},
 	lexer::tokens::{
-		token::Token,
+		token::{Span, Token},
 		token_type::{self, TokenType},
 	},
+	stack,
 };
 
+/// How deeply expressions and statements can be nested in each other by default
+pub const DEFAULT_MAX_DEPTH: usize = 200;
+
 pub struct Parser<'p, I: Iterator<Item = Token<'p>>> {
 	tokens: Box<Peekable<I>>,
 	history: Vec<Token<'p>>,
 	errors: Vec<String>,
+	/// How deeply the statement being parsed is nested so far
+	depth: usize,
+	max_depth: usize,
 }
 impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 	pub fn new(tokens: Box<Peekable<I>>) -> Self {
@@ -24,12 +32,19 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 			tokens,
 			history: Vec::new(),
 			errors: Vec::new(),
+			depth: 0,
+			max_depth: DEFAULT_MAX_DEPTH,
 		};
 		parser.advance();
 		parser
 	}
+	/// How deeply expressions and statements can be nested in each other before it's an error
+	pub fn max_depth(mut self, max_depth: usize) -> Self {
+		self.max_depth = max_depth;
+		self
+	}
 
-	fn get_token_at(&self, rel: usize) -> Result<&TokenType> {
+	fn get_token_at(&self, rel: usize) -> Result<&TokenType<'_>> {
 		let pos = self.history.len().saturating_sub(rel).saturating_sub(1);
 		let token = self
 			.history
@@ -54,12 +69,16 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 				.push(Token::new(token_type::TokenType::EndOfFile, 0, 0))
 		}
 	}
+	/// Where the current token starts
+	fn span(&self) -> Span {
+		self.history.last().map(Token::span).unwrap_or_default()
+	}
 	/// Get current token
-	fn current(&self) -> Result<&TokenType> {
+	fn current(&self) -> Result<&TokenType<'_>> {
 		self.get_token_at(0)
 	}
 	/// Get previous token
-	fn prev(&self) -> Result<&TokenType> {
+	fn prev(&self) -> Result<&TokenType<'_>> {
 		self.get_token_at(1)
 	}
 	/// Check if the current token is of a give type
@@ -67,7 +86,7 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 		Ok(!self.is_at_end() && self.current()? == token)
 	}
 	/// Match the current token against a given list and advance the index (only if there is a match)
-	fn _match_token(&mut self, types: &'p [&TokenType]) -> Result<Option<&TokenType>> {
+	fn _match_token(&mut self, types: &'p [&TokenType]) -> Result<Option<&TokenType<'_>>> {
 		for t in types {
 			if self.check(t)? {
 				self.advance();
@@ -93,33 +112,50 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 			let prev = self.prev()?;
 			let current = self.current()?;
 
-			if prev != &TokenType::Punctuation(Punctuation::Semicolon)
-				&& current != &TokenType::Keyword(Keyword::Class)
-				&& current != &TokenType::Keyword(Keyword::Function)
-				&& current != &TokenType::Keyword(Keyword::Var)
-				&& current != &TokenType::Keyword(Keyword::For)
-				&& current != &TokenType::Keyword(Keyword::If)
-				&& current != &TokenType::Keyword(Keyword::While)
-				&& current != &TokenType::Keyword(Keyword::Print)
-				&& current != &TokenType::Keyword(Keyword::Return)
+			// Stop after the end of the broken statement, or at the start of the next one
+			if prev == &TokenType::Punctuation(Punctuation::Semicolon)
+				|| current == &TokenType::Keyword(Keyword::Class)
+				|| current == &TokenType::Keyword(Keyword::Function)
+				|| current == &TokenType::Keyword(Keyword::Var)
+				|| current == &TokenType::Keyword(Keyword::For)
+				|| current == &TokenType::Keyword(Keyword::If)
+				|| current == &TokenType::Keyword(Keyword::While)
+				|| current == &TokenType::Keyword(Keyword::Print)
+				|| current == &TokenType::Keyword(Keyword::Return)
 			{
-				self.advance();
+				break;
 			}
+			self.advance();
 		}
 		Ok(())
 	}
 
+	/// Parse something nested in what's being parsed, unless that's nested too deeply already
+	fn nested<T>(&mut self, parse: fn(&mut Self) -> Result<T>) -> Result<T> {
+		if self.depth >= self.max_depth {
+			bail!(
+				"Stack overflow: nested more than {} levels deep",
+				self.max_depth
+			);
+		}
+		self.depth += 1;
+		let result = stack::grow(|| parse(self));
+		self.depth -= 1;
+
+		result
+	}
+
 	fn expression(&mut self) -> Result<Expr> {
-		self.assignment()
+		self.nested(Self::assignment)
 	}
 	fn and(&mut self) -> Result<Expr> {
-		let mut expr = self.equality()?;
+		let mut expr = self.bit_or()?;
 
 		while let TokenType::Operator(op @ token_type::Operator::And) = self.current()? {
 			let op = op.clone();
 			self.advance();
 
-			let right_expr = self.equality()?;
+			let right_expr = self.bit_or()?;
 			let right_expr = Box::new(right_expr);
 
 			expr = Expr::Logical(Box::new(expr), op, right_expr);
@@ -140,25 +176,141 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 		}
 		Ok(expr)
 	}
-	fn assignment(&mut self) -> Result<Expr> {
-		let expr = self.or()?;
+	fn pipeline(&mut self) -> Result<Expr> {
+		let mut expr = self.or()?;
 
-		if let TokenType::Operator(token_type::Operator::Eq) = self.current()? {
+		while self.check(&TokenType::Operator(token_type::Operator::Pipeline))? {
+			let span = self.span();
 			self.advance();
 
-			let value = self.assignment()?;
-			let value = Box::new(value);
-
-			if let Expr::Variable(name) = expr {
-				Ok(Expr::Assign(name, value))
+			// `x |> f(a)` is sugar for `f(x, a)` and a bare `x |> f` for `f(x)`
+			let mut call = self.call()?;
+			if let Expr::Call(_, _, args) = &mut call {
+				args.insert(0, expr);
 			} else {
-				let should_be_eq = self.prev()?;
-				bail!("{:?} is an invalid assignment target", &should_be_eq)
+				call = Expr::Call(Box::new(call), span, vec![expr]);
 			}
+			expr = call;
+		}
+		Ok(expr)
+	}
+	fn coalesce(&mut self) -> Result<Expr> {
+		let mut expr = self.pipeline()?;
+
+		while let TokenType::Operator(op @ token_type::Operator::Coalesce) = self.current()? {
+			let op = op.clone();
+			self.advance();
+
+			let right_expr = self.pipeline()?;
+			let right_expr = Box::new(right_expr);
+
+			expr = Expr::Logical(Box::new(expr), op, right_expr);
+		}
+		Ok(expr)
+	}
+	fn conditional(&mut self) -> Result<Expr> {
+		let condition = self.coalesce()?;
+
+		if self.check(&TokenType::Punctuation(token_type::Punctuation::Question))? {
+			self.advance();
+
+			let then_expr = self.expression()?;
+			self.assert_next(
+				&TokenType::Punctuation(token_type::Punctuation::Colon),
+				"Expected a `:` after the conditional's first branch",
+			)?;
+			let else_expr = self.nested(Self::conditional)?;
+
+			Ok(Expr::Conditional(
+				Box::new(condition),
+				Box::new(then_expr),
+				Box::new(else_expr),
+			))
 		} else {
-			Ok(expr)
+			Ok(condition)
+		}
+	}
+	fn assignment(&mut self) -> Result<Expr> {
+		use token_type::Operator;
+
+		let expr = self.conditional()?;
+
+		match self.current()? {
+			TokenType::Operator(Operator::Eq) => {
+				self.advance();
+
+				let value = self.nested(Self::assignment)?;
+				let value = Box::new(value);
+
+				Ok(Expr::Assign(Self::target(expr)?, value))
+			}
+			TokenType::Operator(
+				op @ (Operator::AddEq
+				| Operator::SubEq
+				| Operator::MulEq
+				| Operator::DivEq
+				| Operator::ModEq),
+			) => {
+				let op = op.clone();
+				self.advance();
+
+				let value = self.nested(Self::assignment)?;
+				let value = Box::new(value);
+
+				Ok(Expr::CompoundAssign(Self::target(expr)?, op, value))
+			}
+			_ => Ok(expr),
 		}
 	}
+	/// What an assignment to `expr` writes to, if it can be assigned to at all
+	fn target(mut expr: Expr) -> Result<Target> {
+		// `Expr` can't be taken apart by moving out of it, since it has a `Drop`
+		match &mut expr {
+			Expr::Variable(var) => Ok(Target::Variable(mem::replace(var, Var::new("")))),
+			Expr::Index(target, index) => Ok(Target::Index(take(target), take(index))),
+			// `?.` might skip the assignment, which couldn't return anything sensible
+			Expr::Get(object, name, false) => Ok(Target::Property(take(object), mem::take(name))),
+			_ => bail!("Invalid assignment target"),
+		}
+	}
+	fn bit_or(&mut self) -> Result<Expr> {
+		let mut expr = self.bit_xor()?;
+
+		while self.check(&TokenType::Punctuation(token_type::Punctuation::Pipe))? {
+			self.advance();
+
+			let right = self.bit_xor()?;
+			expr = Expr::Binary(Box::new(expr), token_type::Operator::BitOr, Box::new(right));
+		}
+		Ok(expr)
+	}
+	fn bit_xor(&mut self) -> Result<Expr> {
+		let mut expr = self.bit_and()?;
+
+		while let TokenType::Operator(op @ token_type::Operator::BitXor) = self.current()? {
+			let op = op.clone();
+			self.advance();
+
+			let right = self.bit_and()?;
+			expr = Expr::Binary(Box::new(expr), op, Box::new(right));
+		}
+		Ok(expr)
+	}
+	fn bit_and(&mut self) -> Result<Expr> {
+		let mut expr = self.equality()?;
+
+		while self.check(&TokenType::Punctuation(token_type::Punctuation::Ampersand))? {
+			self.advance();
+
+			let right = self.equality()?;
+			expr = Expr::Binary(
+				Box::new(expr),
+				token_type::Operator::BitAnd,
+				Box::new(right),
+			);
+		}
+		Ok(expr)
+	}
 	fn equality(&mut self) -> Result<Expr> {
 		use token_type::Operator;
 
@@ -176,7 +328,7 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 	fn comparison(&mut self) -> Result<Expr> {
 		use token_type::Operator;
 
-		let mut expr = self.term()?;
+		let mut expr = self.shift()?;
 
 		while let TokenType::Operator(
 			op @ (Operator::Gt | Operator::Gte | Operator::Lt | Operator::Lte),
@@ -185,13 +337,30 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 			let op = op.clone();
 			self.advance();
 
-			let right = self.term()?;
+			let right = self.shift()?;
 
 			expr = Expr::Binary(Box::new(expr), op, Box::new(right));
 		}
 
 		Ok(expr)
 	}
+	fn shift(&mut self) -> Result<Expr> {
+		use token_type::Operator;
+
+		let mut expr = self.term()?;
+
+		while let TokenType::Operator(op @ (Operator::ShiftLeft | Operator::ShiftRight)) =
+			self.current()?
+		{
+			let op = op.clone();
+			self.advance();
+
+			let right = self.term()?;
+
+			expr = Expr::Binary(Box::new(expr), op, Box::new(right));
+		}
+		Ok(expr)
+	}
 	fn term(&mut self) -> Result<Expr> {
 		use token_type::Operator;
 
@@ -212,7 +381,10 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 
 		let mut expr = self.unary()?;
 
-		while let TokenType::Operator(op @ (Operator::Mul | Operator::Div)) = self.current()? {
+		while let TokenType::Operator(
+			op @ (Operator::Mul | Operator::Div | Operator::FloorDiv | Operator::Mod),
+		) = self.current()?
+		{
 			let op = op.clone();
 			self.advance();
 
@@ -222,7 +394,7 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 		}
 		Ok(expr)
 	}
-	fn finish_call(&mut self, callee: Expr) -> Result<Expr> {
+	fn finish_call(&mut self, callee: Expr, span: Span) -> Result<Expr> {
 		let mut args: Vec<Expr> = Vec::new();
 		if self.current()? != &TokenType::Punctuation(token_type::Punctuation::BracketClose) {
 			args.push(self.expression()?);
@@ -236,52 +408,346 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 			&TokenType::Punctuation(token_type::Punctuation::BracketClose),
 			"Expected a `)` after the argument list",
 		)?;
-		Ok(Expr::Call(
-			Box::new(callee),
-			token_type::Punctuation::BracketClose,
-			args,
-		))
+		Ok(Expr::Call(Box::new(callee), span, args))
 	}
 	fn call(&mut self) -> Result<Expr> {
 		let mut expr = self.primary()?;
-		while self.current()? == &TokenType::Punctuation(token_type::Punctuation::BracketOpen) {
-			self.advance();
-			expr = self.finish_call(expr)?;
+		loop {
+			if self.check(&TokenType::Punctuation(
+				token_type::Punctuation::BracketOpen,
+			))? {
+				let span = self.span();
+				self.advance();
+				expr = self.finish_call(expr, span)?;
+			} else if self.check(&TokenType::Punctuation(
+				token_type::Punctuation::BracketSquareOpen,
+			))? {
+				self.advance();
+
+				let index = self.expression()?;
+				self.assert_next(
+					&TokenType::Punctuation(token_type::Punctuation::BracketSquareClose),
+					"Expected a `]` after the index",
+				)?;
+				expr = Expr::Index(Box::new(expr), Box::new(index));
+			} else if let TokenType::Punctuation(token_type::Punctuation::Dot)
+			| TokenType::Operator(token_type::Operator::OptionalChain) = self.current()?
+			{
+				let optional =
+					self.current()? != &TokenType::Punctuation(token_type::Punctuation::Dot);
+				self.advance();
+
+				let &TokenType::Identifier(name) = self.current()? else {
+					bail!(
+						"Expected a property name after `{}`",
+						if optional { "?." } else { "." }
+					);
+				};
+				let name = String::from_utf8(name.to_vec())?;
+				self.advance();
+
+				expr = Expr::Get(Box::new(expr), name, optional);
+			} else {
+				break;
+			}
 		}
 
 		Ok(expr)
 	}
-	fn unary(&mut self) -> Result<Expr> {
+	fn postfix(&mut self) -> Result<Expr> {
 		use token_type::Operator;
 
-		if let TokenType::Operator(op @ (Operator::Not | Operator::Sub | Operator::Add)) =
+		let expr = self.call()?;
+
+		if let TokenType::Operator(op @ (Operator::Increment | Operator::Decrement)) =
 			self.current()?
 		{
 			let op = op.clone();
 			self.advance();
 
-			let right = self.unary()?;
+			Ok(Expr::Postfix(Self::target(expr)?, op))
+		} else {
+			Ok(expr)
+		}
+	}
+	fn power(&mut self) -> Result<Expr> {
+		let expr = self.postfix()?;
+
+		if let TokenType::Operator(op @ token_type::Operator::Pow) = self.current()? {
+			let op = op.clone();
+			self.advance();
 
-			Ok(Expr::Unary(op, Box::new(right)))
+			// Right-associative, and the exponent may carry its own sign (`2 ** -1`)
+			let right = self.nested(Self::unary)?;
+
+			Ok(Expr::Binary(Box::new(expr), op, Box::new(right)))
 		} else {
-			self.call()
+			Ok(expr)
+		}
+	}
+	fn unary(&mut self) -> Result<Expr> {
+		use token_type::Operator;
+
+		match self.current()? {
+			TokenType::Operator(
+				op @ (Operator::Not | Operator::Sub | Operator::Add | Operator::BitNot),
+			) => {
+				let op = op.clone();
+				self.advance();
+
+				let right = self.nested(Self::unary)?;
+
+				Ok(Expr::Unary(op, Box::new(right)))
+			}
+			TokenType::Operator(op @ (Operator::Increment | Operator::Decrement)) => {
+				let op = op.clone();
+				self.advance();
+
+				let target = self.nested(Self::unary)?;
+				Ok(Expr::Prefix(op, Self::target(target)?))
+			}
+			_ => self.power(),
 		}
 	}
+	fn literal(lit: &token_type::Literal) -> Result<Literal> {
+		let value = match lit {
+			token_type::Literal::String(v) => {
+				Literal::String(Shared::new(String::from_utf8(v.to_vec())?))
+			}
+			token_type::Literal::Number(v) => Literal::Number(*v),
+			token_type::Literal::Boolean(v) => Literal::Boolean(*v),
+			token_type::Literal::Null => Literal::Null,
+		};
+		Ok(value)
+	}
+	fn pattern_element(&mut self) -> Result<PatternElement> {
+		let pattern = self.pattern()?;
+
+		let mut default: Option<Expr> = None;
+		if self.check(&TokenType::Operator(token_type::Operator::Eq))? {
+			self.advance();
+			default = Some(self.expression()?);
+		}
+		Ok(PatternElement { pattern, default })
+	}
+	fn list_pattern(&mut self) -> Result<Pattern> {
+		let mut elements: Vec<PatternElement> = Vec::new();
+		let mut rest: Option<String> = None;
+
+		while !self.check(&TokenType::Punctuation(
+			token_type::Punctuation::BracketSquareClose,
+		))? {
+			if self.check(&TokenType::Punctuation(token_type::Punctuation::Ellipsis))? {
+				self.advance();
+
+				let &TokenType::Identifier(name) = self.current()? else {
+					bail!("Expected a name after `...`");
+				};
+				rest = Some(String::from_utf8(name.to_vec())?);
+				self.advance();
+				break;
+			}
+			elements.push(self.nested(Self::pattern_element)?);
+
+			if self.check(&TokenType::Punctuation(token_type::Punctuation::Comma))? {
+				self.advance();
+			} else {
+				break;
+			}
+		}
+		self.assert_next(
+			&TokenType::Punctuation(token_type::Punctuation::BracketSquareClose),
+			"Expected a `]` to close the list pattern",
+		)?;
+
+		Ok(Pattern::List(elements, rest))
+	}
+	fn map_pattern(&mut self) -> Result<Pattern> {
+		let mut entries: Vec<(String, PatternElement)> = Vec::new();
+
+		while !self.check(&TokenType::Punctuation(
+			token_type::Punctuation::BracketCurlyClose,
+		))? {
+			let (key, is_ident) = match self.current()? {
+				TokenType::Identifier(key) => (String::from_utf8(key.to_vec())?, true),
+				TokenType::Literal(token_type::Literal::String(key)) => {
+					(String::from_utf8(key.to_vec())?, false)
+				}
+				other => bail!("Unexpected map pattern key {:?}", &other),
+			};
+			self.advance();
+
+			let pattern = if self.check(&TokenType::Punctuation(token_type::Punctuation::Colon))? {
+				self.advance();
+				self.nested(Self::pattern)?
+			} else if is_ident {
+				Pattern::Binding(key.clone())
+			} else {
+				bail!("Expected a `:` after the map pattern's key");
+			};
+			let mut default: Option<Expr> = None;
+			if self.check(&TokenType::Operator(token_type::Operator::Eq))? {
+				self.advance();
+				default = Some(self.expression()?);
+			}
+			entries.push((key, PatternElement { pattern, default }));
+
+			if self.check(&TokenType::Punctuation(token_type::Punctuation::Comma))? {
+				self.advance();
+			} else {
+				break;
+			}
+		}
+		self.assert_next(
+			&TokenType::Punctuation(token_type::Punctuation::BracketCurlyClose),
+			"Expected a `}` to close the map pattern",
+		)?;
+
+		Ok(Pattern::Map(entries))
+	}
+	fn pattern(&mut self) -> Result<Pattern> {
+		let pattern = match self.current()? {
+			TokenType::Punctuation(token_type::Punctuation::BracketSquareOpen) => {
+				self.advance();
+				return self.list_pattern();
+			}
+			TokenType::Punctuation(token_type::Punctuation::BracketCurlyOpen) => {
+				self.advance();
+				return self.map_pattern();
+			}
+			TokenType::Identifier(b"_") => Pattern::Wildcard,
+			TokenType::Identifier(ident) => Pattern::Binding(String::from_utf8(ident.to_vec())?),
+			TokenType::Literal(lit) => Pattern::Literal(Self::literal(lit)?),
+			TokenType::Operator(token_type::Operator::Sub) => {
+				self.advance();
+
+				let &TokenType::Literal(token_type::Literal::Number(n)) = self.current()? else {
+					bail!("Expected a number after `-` in a pattern");
+				};
+				Pattern::Literal(Literal::Number(-n))
+			}
+			other => bail!("Unexpected pattern {:?}", &other),
+		};
+		self.advance();
+
+		Ok(pattern)
+	}
+	fn match_expr(&mut self) -> Result<Expr> {
+		let value = self.expression()?;
+		self.assert_next(
+			&TokenType::Punctuation(token_type::Punctuation::BracketCurlyOpen),
+			"Expected a `{` after the `match` value",
+		)?;
+
+		let mut arms: Vec<MatchArm> = Vec::new();
+		while !self.check(&TokenType::Punctuation(
+			token_type::Punctuation::BracketCurlyClose,
+		))? {
+			let pattern = self.pattern()?;
+
+			let mut guard: Option<Expr> = None;
+			if self.check(&TokenType::Keyword(token_type::Keyword::If))? {
+				self.advance();
+				guard = Some(self.expression()?);
+			}
+			self.assert_next(
+				&TokenType::Operator(token_type::Operator::Arrow),
+				"Expected a `=>` after the `match` arm's pattern",
+			)?;
+			let body = self.expression()?;
+
+			arms.push(MatchArm {
+				pattern,
+				guard,
+				body,
+			});
+
+			if self.check(&TokenType::Punctuation(token_type::Punctuation::Comma))? {
+				self.advance();
+			} else {
+				break;
+			}
+		}
+		self.assert_next(
+			&TokenType::Punctuation(token_type::Punctuation::BracketCurlyClose),
+			"Expected a `}` to close the `match` arms",
+		)?;
+
+		Ok(Expr::Match(Box::new(value), arms))
+	}
+	fn list(&mut self) -> Result<Expr> {
+		let mut items: Vec<Expr> = Vec::new();
+
+		while !self.check(&TokenType::Punctuation(
+			token_type::Punctuation::BracketSquareClose,
+		))? {
+			items.push(self.expression()?);
+
+			if self.check(&TokenType::Punctuation(token_type::Punctuation::Comma))? {
+				self.advance();
+			} else {
+				break;
+			}
+		}
+		self.assert_next(
+			&TokenType::Punctuation(token_type::Punctuation::BracketSquareClose),
+			"Expected a `]` to close the list",
+		)?;
+
+		Ok(Expr::List(items))
+	}
+	fn map(&mut self) -> Result<Expr> {
+		let mut entries: Vec<(String, Expr)> = Vec::new();
+
+		while !self.check(&TokenType::Punctuation(
+			token_type::Punctuation::BracketCurlyClose,
+		))? {
+			let key = match self.current()? {
+				TokenType::Identifier(key)
+				| TokenType::Literal(token_type::Literal::String(key)) => String::from_utf8(key.to_vec())?,
+				other => bail!("Unexpected map key {:?}", &other),
+			};
+			self.advance();
+
+			self.assert_next(
+				&TokenType::Punctuation(token_type::Punctuation::Colon),
+				"Expected a `:` after the map key",
+			)?;
+			entries.push((key, self.expression()?));
+
+			if self.check(&TokenType::Punctuation(token_type::Punctuation::Comma))? {
+				self.advance();
+			} else {
+				break;
+			}
+		}
+		self.assert_next(
+			&TokenType::Punctuation(token_type::Punctuation::BracketCurlyClose),
+			"Expected a `}` to close the map",
+		)?;
+
+		Ok(Expr::Map(entries))
+	}
 	fn primary(&mut self) -> Result<Expr> {
 		match self.current()? {
 			TokenType::Literal(lit) => {
-				let value = match lit {
-					token_type::Literal::String(v) => {
-						Literal::String(String::from_utf8(v.to_vec())?)
-					}
-					token_type::Literal::Number(v) => Literal::Number(*v),
-					token_type::Literal::Boolean(v) => Literal::Boolean(*v),
-					token_type::Literal::Null => Literal::Null,
-				};
+				let value = Self::literal(lit)?;
 
 				self.advance();
 				Ok(Expr::Literal(value))
 			}
+			TokenType::Keyword(token_type::Keyword::Match) => {
+				self.advance();
+				self.match_expr()
+			}
+			TokenType::Punctuation(token_type::Punctuation::BracketSquareOpen) => {
+				self.advance();
+				self.list()
+			}
+			TokenType::Punctuation(token_type::Punctuation::BracketCurlyOpen) => {
+				self.advance();
+				self.map()
+			}
 			TokenType::Punctuation(token_type::Punctuation::BracketOpen) => {
 				self.advance();
 
@@ -297,7 +763,7 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 				let ident = String::from_utf8(ident.to_vec())?;
 				self.advance();
 
-				Ok(Expr::Variable(ident))
+				Ok(Expr::Variable(Var::new(ident)))
 			}
 			other => {
 				bail!("Unknown primary expression received: {:?}", &other);
@@ -327,7 +793,7 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 		while !self.check(&TokenType::Punctuation(
 			token_type::Punctuation::BracketCurlyClose,
 		))? {
-			let decl = self.declaration()?;
+			let decl = self.nested(Self::declaration)?;
 			statments.push(decl);
 		}
 		self.assert_next(
@@ -348,12 +814,12 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 			"Expected a `)` after the `if` condition",
 		)?;
 
-		let then_branch = self.statement()?;
+		let then_branch = self.nested(Self::statement)?;
 		let then_branch = Box::new(then_branch);
 
-		let else_branch = if self.current()? == &TokenType::Keyword(token_type::Keyword::If) {
+		let else_branch = if self.current()? == &TokenType::Keyword(token_type::Keyword::Else) {
 			self.advance();
-			let block = self.statement()?;
+			let block = self.nested(Self::statement)?;
 			let block = Box::new(block);
 			Some(block)
 		} else {
@@ -372,7 +838,7 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 			&TokenType::Punctuation(token_type::Punctuation::BracketClose),
 			"Expected a `)` after the `while` condition",
 		)?;
-		let body = self.statement()?;
+		let body = self.nested(Self::statement)?;
 
 		Ok(Stmt::While(condition, Box::new(body)))
 	}
@@ -397,10 +863,10 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 			};
 		let condition: Option<Expr> =
 			if self.check(&TokenType::Punctuation(token_type::Punctuation::Semicolon))? {
+				None
+			} else {
 				let expr = self.expression()?;
 				Some(expr)
-			} else {
-				None
 			};
 		self.assert_next(
 			&TokenType::Punctuation(token_type::Punctuation::Semicolon),
@@ -409,16 +875,16 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 		let increment = if self.check(&TokenType::Punctuation(
 			token_type::Punctuation::BracketClose,
 		))? {
+			None
+		} else {
 			let expr = self.expression()?;
 			Some(expr)
-		} else {
-			None
 		};
 		self.assert_next(
 			&TokenType::Punctuation(token_type::Punctuation::BracketClose),
 			"Expected a `)` after the `for` clause",
 		)?;
-		let body = self.statement()?;
+		let body = self.nested(Self::statement)?;
 		let body = Box::new(body);
 
 		Ok(Stmt::For(initializer, condition, increment, body))
@@ -435,6 +901,60 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 		)?;
 		Ok(Stmt::Return(value))
 	}
+	fn throw_stmt(&mut self) -> Result<Stmt> {
+		let value = self.expression()?;
+		self.assert_next(
+			&TokenType::Punctuation(token_type::Punctuation::Semicolon),
+			"Expected `;` after the thrown value",
+		)?;
+		Ok(Stmt::Throw(value))
+	}
+	fn try_stmt(&mut self) -> Result<Stmt> {
+		self.assert_next(
+			&TokenType::Punctuation(token_type::Punctuation::BracketCurlyOpen),
+			"Expected a `{` after `try`",
+		)?;
+		let body = self.block()?;
+
+		let catch = if self.check(&TokenType::Keyword(token_type::Keyword::Catch))? {
+			self.advance();
+			self.assert_next(
+				&TokenType::Punctuation(token_type::Punctuation::BracketOpen),
+				"Expected a `(` after `catch`",
+			)?;
+			let &TokenType::Identifier(name) = self.current()? else {
+				bail!("Expected a name for the caught error");
+			};
+			let name = String::from_utf8(name.to_vec())?;
+			self.advance();
+			self.assert_next(
+				&TokenType::Punctuation(token_type::Punctuation::BracketClose),
+				"Expected a `)` after the caught error's name",
+			)?;
+			self.assert_next(
+				&TokenType::Punctuation(token_type::Punctuation::BracketCurlyOpen),
+				"Expected a `{` after `catch`",
+			)?;
+			Some((name, Box::new(self.block()?)))
+		} else {
+			None
+		};
+		let finally = if self.check(&TokenType::Keyword(token_type::Keyword::Finally))? {
+			self.advance();
+			self.assert_next(
+				&TokenType::Punctuation(token_type::Punctuation::BracketCurlyOpen),
+				"Expected a `{` after `finally`",
+			)?;
+			Some(Box::new(self.block()?))
+		} else {
+			None
+		};
+
+		if catch.is_none() && finally.is_none() {
+			bail!("Expected a `catch` or a `finally` after the `try` block");
+		}
+		Ok(Stmt::Try(Box::new(body), catch, finally))
+	}
 	fn statement(&mut self) -> Result<Stmt> {
 		if self.check(&TokenType::Keyword(token_type::Keyword::If))? {
 			self.advance();
@@ -448,6 +968,26 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 		} else if self.check(&TokenType::Keyword(token_type::Keyword::While))? {
 			self.advance();
 			self.while_stmt()
+		} else if self.check(&TokenType::Keyword(token_type::Keyword::Throw))? {
+			self.advance();
+			self.throw_stmt()
+		} else if self.check(&TokenType::Keyword(token_type::Keyword::Try))? {
+			self.advance();
+			self.try_stmt()
+		} else if self.check(&TokenType::Keyword(token_type::Keyword::Break))? {
+			self.advance();
+			self.assert_next(
+				&TokenType::Punctuation(token_type::Punctuation::Semicolon),
+				"Expected `;` after `break`",
+			)?;
+			Ok(Stmt::Break)
+		} else if self.check(&TokenType::Keyword(token_type::Keyword::Continue))? {
+			self.advance();
+			self.assert_next(
+				&TokenType::Punctuation(token_type::Punctuation::Semicolon),
+				"Expected `;` after `continue`",
+			)?;
+			Ok(Stmt::Continue)
 		} else if self.check(&TokenType::Keyword(token_type::Keyword::For))? {
 			self.advance();
 			self.for_stmt()
@@ -461,7 +1001,23 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 		}
 	}
 	fn var_declaration(&mut self) -> Result<Stmt> {
-		if let &TokenType::Identifier(ident) = self.current()? {
+		if let TokenType::Punctuation(
+			token_type::Punctuation::BracketSquareOpen | token_type::Punctuation::BracketCurlyOpen,
+		) = self.current()?
+		{
+			let pattern = self.pattern()?;
+			self.assert_next(
+				&TokenType::Operator(token_type::Operator::Eq),
+				"Expected a `=` after the destructuring pattern",
+			)?;
+			let value = self.expression()?;
+
+			self.assert_next(
+				&TokenType::Punctuation(token_type::Punctuation::Semicolon),
+				"Expected a `;` after the variable initialization",
+			)?;
+			Ok(Stmt::Destructure(pattern, value))
+		} else if let &TokenType::Identifier(ident) = self.current()? {
 			let ident = String::from_utf8(ident.to_vec())?;
 			self.advance();
 
@@ -492,46 +1048,44 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 			"Expected a `(` after the function's name",
 		)?;
 
-		if !self.check(&TokenType::Punctuation(
+		let mut params = Params::default();
+		while !self.check(&TokenType::Punctuation(
 			token_type::Punctuation::BracketClose,
 		))? {
-			let mut params: Vec<Expr> = Vec::new();
-			loop {
-				if let &TokenType::Identifier(param_name) = self.current()? {
-					let param_name = String::from_utf8(param_name.to_vec())?;
-					params.push(Expr::Variable(param_name));
-					self.advance();
-				}
+			if self.check(&TokenType::Punctuation(token_type::Punctuation::Ellipsis))? {
+				self.advance();
 
-				if self.check(&TokenType::Punctuation(token_type::Punctuation::Comma))? {
-					self.advance();
-				} else {
-					break;
-				}
+				let &TokenType::Identifier(name) = self.current()? else {
+					bail!("Expected a parameter name after `...`");
+				};
+				params.rest = Some(String::from_utf8(name.to_vec())?);
+				self.advance();
+				break;
 			}
-			self.assert_next(
-				&TokenType::Punctuation(token_type::Punctuation::BracketClose),
-				"Expected a `)` after the function's argument list",
-			)?;
 
-			if self.check(&TokenType::Punctuation(
-				token_type::Punctuation::BracketCurlyOpen,
-			))? {
-				self.advance();
+			let param = self.pattern_element()?;
+			if param.default.is_none() && params.elements.iter().any(|p| p.default.is_some()) {
+				bail!("A parameter without a default value can't follow one with a default");
+			}
+			params.elements.push(param);
 
-				let block = self.block()?;
-				return Ok(Stmt::Function(fn_name, params, Box::new(block)));
+			if self.check(&TokenType::Punctuation(token_type::Punctuation::Comma))? {
+				self.advance();
 			} else {
-				bail!("Expected here a block start - `{{`");
+				break;
 			}
 		}
 		self.assert_next(
 			&TokenType::Punctuation(token_type::Punctuation::BracketClose),
-			"Expected a `)` after the parameter list",
+			"Expected a `)` after the function's parameter list",
+		)?;
+		self.assert_next(
+			&TokenType::Punctuation(token_type::Punctuation::BracketCurlyOpen),
+			"Expected here a block start - `{`",
 		)?;
 
 		let block = self.block()?;
-		Ok(Stmt::Function(fn_name, Vec::new(), Box::new(block)))
+		Ok(Stmt::Function(fn_name, params, Rc::new(block)))
 	}
 	fn declaration(&mut self) -> Result<Stmt> {
 		if self.check(&TokenType::Keyword(token_type::Keyword::Var))? {
@@ -563,3 +1117,8 @@ impl<'p, I: Iterator<Item = Token<'p>>> Parser<'p, I> {
 
 #[cfg(test)]
 mod tests;
+
+/// Take an expression out of the tree, leaving `null` behind
+fn take(expr: &mut Box<Expr>) -> Box<Expr> {
+	mem::replace(expr, Box::new(Expr::Literal(Literal::Null)))
+}
@@ -1,12 +1,18 @@
+use std::rc::Rc;
+
 use anyhow::Result;
 
 use super::{
 	super::{
 		ast::{
-			expr::{Expr, Literal},
+			expr::{Expr, Literal, Shared, Target, Var},
+			pattern::{MatchArm, Params, Pattern, PatternElement},
 			stmt::Stmt,
 		},
-		lexer::{scanner::scan, tokens::token_type::Operator},
+		lexer::{
+			scanner::scan,
+			tokens::{token::Span, token_type::Operator},
+		},
 	},
 	Parser,
 };
@@ -70,3 +76,498 @@ fn one_plus_one_mul_one() -> Result<()> {
 
 	Ok(())
 }
+
+#[test]
+fn conditional_is_right_associative() -> Result<()> {
+	let input = b"a ? 1 : b ? 2 : 3;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Conditional(
+			Box::new(Expr::Variable(Var::new("a"))),
+			Box::new(Expr::Literal(Literal::Number(1.))),
+			Box::new(Expr::Conditional(
+				Box::new(Expr::Variable(Var::new("b"))),
+				Box::new(Expr::Literal(Literal::Number(2.))),
+				Box::new(Expr::Literal(Literal::Number(3.))),
+			)),
+		))]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn coalesce_binds_looser_than_or() -> Result<()> {
+	let input = b"a ?? b || c;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Logical(
+			Box::new(Expr::Variable(Var::new("a"))),
+			Operator::Coalesce,
+			Box::new(Expr::Logical(
+				Box::new(Expr::Variable(Var::new("b"))),
+				Operator::Or,
+				Box::new(Expr::Variable(Var::new("c"))),
+			)),
+		))]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn compound_assignment() -> Result<()> {
+	let input = b"i += 2 * 3;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::CompoundAssign(
+			Target::Variable(Var::new("i")),
+			Operator::AddEq,
+			Box::new(Expr::Binary(
+				Box::new(Expr::Literal(Literal::Number(2.))),
+				Operator::Mul,
+				Box::new(Expr::Literal(Literal::Number(3.)))
+			)),
+		))]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn prefix_and_postfix_updates() -> Result<()> {
+	let input = b"++i - j--;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Binary(
+			Box::new(Expr::Prefix(
+				Operator::Increment,
+				Target::Variable(Var::new("i"))
+			)),
+			Operator::Sub,
+			Box::new(Expr::Postfix(
+				Target::Variable(Var::new("j")),
+				Operator::Decrement
+			)),
+		))]
+	);
+
+	let input = scan(b"xs[0]++; 1++; f() += 1; ++(a);");
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Postfix(
+			Target::Index(
+				Box::new(Expr::Variable(Var::new("xs"))),
+				Box::new(Expr::Literal(Literal::Number(0.)))
+			),
+			Operator::Increment
+		))]
+	);
+	assert_eq!(errors, &["Invalid assignment target"; 3]);
+
+	Ok(())
+}
+
+#[test]
+fn power_binds_tighter_than_unary_minus() -> Result<()> {
+	let input = b"-2 ** 3 ** 2;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Unary(
+			Operator::Sub,
+			Box::new(Expr::Binary(
+				Box::new(Expr::Literal(Literal::Number(2.))),
+				Operator::Pow,
+				Box::new(Expr::Binary(
+					Box::new(Expr::Literal(Literal::Number(3.))),
+					Operator::Pow,
+					Box::new(Expr::Literal(Literal::Number(2.))),
+				)),
+			)),
+		))]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn modulo_and_floor_div_are_factors() -> Result<()> {
+	let input = b"1 + 7 // 2 % 3;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Binary(
+			Box::new(Expr::Literal(Literal::Number(1.))),
+			Operator::Add,
+			Box::new(Expr::Binary(
+				Box::new(Expr::Binary(
+					Box::new(Expr::Literal(Literal::Number(7.))),
+					Operator::FloorDiv,
+					Box::new(Expr::Literal(Literal::Number(2.))),
+				)),
+				Operator::Mod,
+				Box::new(Expr::Literal(Literal::Number(3.))),
+			)),
+		))]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn bitwise_precedence() -> Result<()> {
+	let input = b"a | b ^ c & d == 1 << 2;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Binary(
+			Box::new(Expr::Variable(Var::new("a"))),
+			Operator::BitOr,
+			Box::new(Expr::Binary(
+				Box::new(Expr::Variable(Var::new("b"))),
+				Operator::BitXor,
+				Box::new(Expr::Binary(
+					Box::new(Expr::Variable(Var::new("c"))),
+					Operator::BitAnd,
+					Box::new(Expr::Binary(
+						Box::new(Expr::Variable(Var::new("d"))),
+						Operator::EqEq,
+						Box::new(Expr::Binary(
+							Box::new(Expr::Literal(Literal::Number(1.))),
+							Operator::ShiftLeft,
+							Box::new(Expr::Literal(Literal::Number(2.))),
+						)),
+					)),
+				)),
+			)),
+		))]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn pipeline_desugars_to_calls() -> Result<()> {
+	let input = b"path |> read |> split(',');";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Call(
+			Box::new(Expr::Variable(Var::new("split"))),
+			Span {
+				line: 1,
+				column: 22
+			},
+			vec![
+				Expr::Call(
+					Box::new(Expr::Variable(Var::new("read"))),
+					Span { line: 1, column: 6 },
+					vec![Expr::Variable(Var::new("path"))],
+				),
+				Expr::Literal(Literal::String(Shared::new(",".to_string()))),
+			],
+		))]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn match_arms() -> Result<()> {
+	let input = b"match x { -1 => 'neg', n if n > 9 => n, _ => null, };";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Match(
+			Box::new(Expr::Variable(Var::new("x"))),
+			vec![
+				MatchArm {
+					pattern: Pattern::Literal(Literal::Number(-1.)),
+					guard: None,
+					body: Expr::Literal(Literal::String(Shared::new("neg".to_string()))),
+				},
+				MatchArm {
+					pattern: Pattern::Binding("n".to_string()),
+					guard: Some(Expr::Binary(
+						Box::new(Expr::Variable(Var::new("n"))),
+						Operator::Gt,
+						Box::new(Expr::Literal(Literal::Number(9.))),
+					)),
+					body: Expr::Variable(Var::new("n")),
+				},
+				MatchArm {
+					pattern: Pattern::Wildcard,
+					guard: None,
+					body: Expr::Literal(Literal::Null),
+				},
+			],
+		))]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn destructuring_declarations() -> Result<()> {
+	let input = b"var [a, b = 2, ...rest] = xs; var {x, 'y': [y] = []} = point;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[
+			Stmt::Destructure(
+				Pattern::List(
+					vec![
+						PatternElement {
+							pattern: Pattern::Binding("a".to_string()),
+							default: None,
+						},
+						PatternElement {
+							pattern: Pattern::Binding("b".to_string()),
+							default: Some(Expr::Literal(Literal::Number(2.))),
+						},
+					],
+					Some("rest".to_string()),
+				),
+				Expr::Variable(Var::new("xs")),
+			),
+			Stmt::Destructure(
+				Pattern::Map(vec![
+					(
+						"x".to_string(),
+						PatternElement {
+							pattern: Pattern::Binding("x".to_string()),
+							default: None,
+						},
+					),
+					(
+						"y".to_string(),
+						PatternElement {
+							pattern: Pattern::List(
+								vec![PatternElement {
+									pattern: Pattern::Binding("y".to_string()),
+									default: None,
+								}],
+								None,
+							),
+							default: Some(Expr::List(Vec::new())),
+						},
+					),
+				]),
+				Expr::Variable(Var::new("point")),
+			),
+		]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn default_and_rest_parameters() -> Result<()> {
+	let input = b"fn f(a, b = 10, ...rest) {}";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty());
+	assert_eq!(
+		tree,
+		&[Stmt::Function(
+			"f".to_string(),
+			Params {
+				elements: vec![
+					PatternElement {
+						pattern: Pattern::Binding("a".to_string()),
+						default: None,
+					},
+					PatternElement {
+						pattern: Pattern::Binding("b".to_string()),
+						default: Some(Expr::Literal(Literal::Number(10.))),
+					},
+				],
+				rest: Some("rest".to_string()),
+			},
+			Rc::new(Stmt::Block(Vec::new())),
+		)]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn required_parameter_after_a_default_one() -> Result<()> {
+	let input = b"fn f(a = 1, b) {}";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (_, errors) = parser.parse()?;
+
+	assert_eq!(
+		errors,
+		&["A parameter without a default value can't follow one with a default"]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn recovers_after_an_error() -> Result<()> {
+	let input = b"var ; print 1; 1 +; print 2;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert_eq!(errors.len(), 2, "{errors:?}");
+	assert_eq!(
+		tree,
+		&[
+			Stmt::Print(Expr::Literal(Literal::Number(1.))),
+			Stmt::Print(Expr::Literal(Literal::Number(2.))),
+		]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn try_needs_a_catch_or_finally() -> Result<()> {
+	let input = b"try { 1; }";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (_, errors) = parser.parse()?;
+
+	assert_eq!(
+		errors,
+		&["Expected a `catch` or a `finally` after the `try` block"]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn nesting_is_limited() -> Result<()> {
+	// The expression statement itself is the first level
+	let input = scan(b"(((1))); ((((1))));");
+
+	let mut parser = Parser::new(input).max_depth(4);
+	let (tree, errors) = parser.parse()?;
+
+	assert_eq!(errors, &["Stack overflow: nested more than 4 levels deep"]);
+	assert_eq!(tree.len(), 1);
+
+	let nested = format!("{}1{};", "(".repeat(300), ")".repeat(300));
+	let input = scan(nested.as_bytes());
+
+	let mut parser = Parser::new(input);
+	let (_, errors) = parser.parse()?;
+
+	assert_eq!(
+		errors,
+		&["Stack overflow: nested more than 200 levels deep"]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn property_access_and_assignment() -> Result<()> {
+	let input = b"a?.b.c = d.e;";
+	let input = scan(input);
+
+	let mut parser = Parser::new(input);
+	let (tree, errors) = parser.parse()?;
+
+	assert!(errors.is_empty(), "{errors:?}");
+	assert_eq!(
+		tree,
+		&[Stmt::Expression(Expr::Assign(
+			Target::Property(
+				Box::new(Expr::Get(
+					Box::new(Expr::Variable(Var::new("a"))),
+					"b".to_string(),
+					true
+				)),
+				"c".to_string()
+			),
+			Box::new(Expr::Get(
+				Box::new(Expr::Variable(Var::new("d"))),
+				"e".to_string(),
+				false
+			)),
+		))]
+	);
+
+	let input = scan(b"a?.b = 1; a.1;");
+	let mut parser = Parser::new(input);
+	let (_, errors) = parser.parse()?;
+
+	assert_eq!(
+		errors,
+		&[
+			"Invalid assignment target",
+			"Expected a property name after `.`"
+		]
+	);
+
+	Ok(())
+}
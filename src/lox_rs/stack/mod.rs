@@ -0,0 +1,24 @@
+use std::mem;
+
+/// How much of the Rust stack has to be left before anything that recurses carries on. It has to
+/// fit everything that happens until the next check, which is a lot more in a debug build.
+const RED_ZONE: usize = 256 * 1024;
+/// How much stack to set aside whenever the current one runs low
+const SEGMENT: usize = 4 * 1024 * 1024;
+
+/// Run `f`, moving on to a new piece of stack first if this one is about to run out. Parsing,
+/// resolving and running a program recurse once per level of its nesting and go through here,
+/// and so does dropping its tree and its values, so nesting is only limited by what's
+/// configured. Printing the tree with `--dump-ast` still recurses on the thread's own stack.
+pub fn grow<R>(f: impl FnOnce() -> R) -> R {
+	stacker::maybe_grow(RED_ZONE, SEGMENT, f)
+}
+/// Drop `value` on a new piece of stack if this one is about to run out, leaving `empty` in its
+/// place. Types that own more of themselves call this from `Drop`: what they own is only
+/// dropped after `drop` returns, too late to go through [`grow`].
+pub fn drop_with_room<T>(value: &mut T, empty: impl FnOnce() -> T) {
+	if stacker::remaining_stack().is_some_and(|left| left < RED_ZONE) {
+		let value = mem::replace(value, empty());
+		stacker::grow(SEGMENT, || drop(value));
+	}
+}
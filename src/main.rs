@@ -1,15 +1,20 @@
 #![deny(clippy::all)]
 
-mod lox_rs;
-
-use std::{fs, path::PathBuf};
+use std::{
+	fs,
+	path::PathBuf,
+	process,
+	slice,
+	time::{Duration, Instant},
+};
 
 use anyhow::{bail, Result};
-use clap::{arg, command, Parser};
+use clap::Parser;
 use lox_rs::{
-	ast::visitors::interp::Interperter,
-	lexer::scanner::scan,
-	parser::Parser as ASTParser,
+	ast::visitors::interp::{DEFAULT_MAX_CALL_DEPTH, DEFAULT_MEMORY_LIMIT},
+	error::CompileError,
+	parser::DEFAULT_MAX_DEPTH,
+	Lox,
 };
 
 #[derive(Parser, Debug)]
@@ -22,8 +27,31 @@ pub struct CLI {
 	pub check_only: bool,
 	#[arg(long)]
 	pub dump_ast: bool,
+	/// Keep running the rest of the program after a statement fails
+	#[arg(long)]
+	pub keep_going: bool,
+	/// How many calls can be nested before the program fails with a stack overflow
+	#[arg(long, default_value_t = DEFAULT_MAX_CALL_DEPTH)]
+	pub max_call_depth: usize,
+	/// How deeply expressions and statements can be nested in the source
+	#[arg(long, default_value_t = DEFAULT_MAX_DEPTH)]
+	pub max_nesting_depth: usize,
+	/// Stop the program after evaluating this many statements and expressions
+	#[arg(long)]
+	pub fuel: Option<u64>,
+	/// Stop the program after running for this many seconds
+	#[arg(long)]
+	pub timeout: Option<f64>,
+	/// How many bytes the strings, lists and maps a program makes can take up in all
+	#[arg(long, default_value_t = DEFAULT_MEMORY_LIMIT)]
+	pub memory_limit: usize,
 }
 
+/// The input couldn't be parsed or resolved, like jlox (`EX_DATAERR`)
+const EXIT_COMPILE_ERROR: i32 = 65;
+/// A statement failed while running, like jlox (`EX_SOFTWARE`)
+const EXIT_RUNTIME_ERROR: i32 = 70;
+
 fn main() -> Result<()> {
 	env_logger::init();
 
@@ -32,35 +60,67 @@ fn main() -> Result<()> {
 		eval,
 		check_only,
 		dump_ast,
+		keep_going,
+		max_call_depth,
+		max_nesting_depth,
+		fuel,
+		timeout,
+		memory_limit,
 	} = CLI::parse();
 
-	let mut interp = Interperter::default();
-	let input = if let Some(ref filepath) = filepath {
-		fs::read(filepath)?
-	} else if let Some(ref input) = eval {
-		input.as_bytes().to_vec()
+	let source = if let Some(filepath) = filepath {
+		fs::read_to_string(filepath)?
+	} else if let Some(input) = eval {
+		input
 	} else {
 		bail!("Could not find source code");
 	};
-	let input = scan(&input);
 
-	let mut parser = ASTParser::new(input);
-	let (tree, errors) = parser.parse()?;
+	let mut lox = Lox::new().max_nesting_depth(max_nesting_depth);
+	let program = match lox.compile(&source) {
+		Ok(program) => program,
+		Err(error) => {
+			let Some(CompileError { errors }) = error.downcast_ref() else {
+				return Err(error);
+			};
+			for error in errors {
+				eprintln!("{error}");
+			}
+			process::exit(EXIT_COMPILE_ERROR);
+		}
+	};
 
-	if !errors.is_empty() {
-		bail!("{:?}", &errors);
+	let interp = lox.interpreter();
+	interp.max_call_depth = max_call_depth;
+	interp.fuel = fuel;
+	interp.memory_limit = Some(memory_limit);
+	if let Some(timeout) = timeout {
+		interp.deadline = Some(Instant::now() + Duration::try_from_secs_f64(timeout)?);
 	}
 
-	for stmt in tree {
+	let mut failed = false;
+	for stmt in &program {
 		if dump_ast {
 			println!("{:#?}", &stmt);
 		}
 		if !check_only {
-			if let Err(e) = stmt.interpret(&mut interp) {
+			if let Err(e) = lox.run(slice::from_ref(stmt)) {
+				if let Some(code) = e.exit_code() {
+					process::exit(code);
+				}
 				eprintln!("{e}");
+				failed = true;
+
+				// Anything after it would be out of budget too
+				if !keep_going || e.interrupted().is_some() {
+					break;
+				}
 			}
 		}
 	}
+	if failed {
+		process::exit(EXIT_RUNTIME_ERROR);
+	}
 
 	Ok(())
 }